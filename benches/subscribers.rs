@@ -0,0 +1,58 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use eventuals::*;
+
+// Subscribing and unsubscribing while many other readers stay subscribed.
+fn churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("subscribe_unsubscribe");
+    for subscribers in [10, 1_000, 10_000] {
+        let (mut writer, eventual) = Eventual::<u32>::new();
+        writer.write(0);
+        let _readers: Vec<_> = (0..subscribers).map(|_| eventual.subscribe()).collect();
+        group.bench_with_input(
+            BenchmarkId::from_parameter(subscribers),
+            &eventual,
+            |b, eventual| b.iter(|| drop(eventual.subscribe())),
+        );
+    }
+    group.finish();
+}
+
+// Subscribing many readers from scratch, then dropping them all.
+fn fill(c: &mut Criterion) {
+    let mut group = c.benchmark_group("subscribe_all");
+    group.sample_size(10);
+    for subscribers in [1_000, 10_000] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(subscribers),
+            &subscribers,
+            |b, &subscribers| {
+                let eventual = Eventual::from_value(0u32);
+                b.iter(|| {
+                    let readers: Vec<_> = (0..subscribers).map(|_| eventual.subscribe()).collect();
+                    drop(readers);
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+// Writing with many subscribers which are not waiting on a value.
+fn write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write");
+    for subscribers in [10, 1_000, 10_000] {
+        let (mut writer, eventual) = Eventual::<u32>::new();
+        let _readers: Vec<_> = (0..subscribers).map(|_| eventual.subscribe()).collect();
+        let mut i = 0;
+        group.bench_function(BenchmarkId::from_parameter(subscribers), |b| {
+            b.iter(|| {
+                i += 1;
+                writer.write(i);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, churn, fill, write);
+criterion_main!(benches);
@@ -0,0 +1,222 @@
+//! Combinators for LocalEventual. These mirror the combinators of the same
+//! name for Eventual, but spawn their tasks with `tokio::task::spawn_local`
+//! so that neither the values nor the closures need to be Send.
+
+use crate::{
+    dedup::{ByEq, Dedup},
+    error::catch_panic,
+    eventual::spawn_local,
+    runtime::default_timer,
+    *,
+};
+use never::Never;
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::select;
+
+/// Applies an operation to each observed snapshot from the source.
+/// See also `eventuals::map`.
+pub fn map<E, I, O, F, Fut>(source: E, f: F) -> LocalEventual<O>
+where
+    E: LocalIntoReader<Output = I>,
+    F: 'static + FnMut(I) -> Fut,
+    I: LocalValue,
+    O: LocalValue + PartialEq,
+    Fut: Future<Output = O>,
+{
+    map_with_dedup(source, ByEq, f)
+}
+
+/// Like `map`, but with a specific dedup strategy for the output.
+/// See also `eventuals::map_with_dedup`.
+pub fn map_with_dedup<E, I, O, D, F, Fut>(source: E, dedup: D, mut f: F) -> LocalEventual<O>
+where
+    E: LocalIntoReader<Output = I>,
+    D: Dedup<O>,
+    F: 'static + FnMut(I) -> Fut,
+    I: LocalValue,
+    O: LocalValue,
+    Fut: Future<Output = O>,
+{
+    let mut source = source.into_reader();
+
+    LocalEventual::spawn_stage_with_dedup(
+        "eventuals::map",
+        Arc::new(dedup),
+        |mut writer| async move {
+            loop {
+                let next = source.next_snapshot_or_close(&writer).await?;
+                let written_at = next.written_at;
+                match catch_panic(|| f(next.into_value())).await {
+                    Ok(value) => writer.write_at(value, written_at),
+                    Err(panic) => writer.handle_panic(panic, true)?,
+                }
+            }
+        },
+    )
+}
+
+/// Indicates the type can be used with the local join method. Not intended
+/// to be used directly.
+pub trait LocalJoinable {
+    type Output;
+
+    fn join(self) -> LocalEventual<Self::Output>
+    where
+        Self: Sized,
+        Self::Output: PartialEq,
+    {
+        self.join_with_dedup(ByEq)
+    }
+
+    /// Like `join`, but with a specific dedup strategy for the output.
+    fn join_with_dedup<D>(self, dedup: D) -> LocalEventual<Self::Output>
+    where
+        D: Dedup<Self::Output>;
+}
+
+macro_rules! impl_tuple {
+    ($len:expr, $($T:ident, $t:ident),*) => {
+        impl<$($T,)*> LocalJoinable for ($($T,)*)
+            where
+                $($T: LocalIntoReader,)*
+        {
+            type Output = ($($T::Output),*);
+
+            #[allow(non_snake_case)]
+            fn join_with_dedup<Dd>(self, dedup: Dd) -> LocalEventual<Self::Output>
+            where
+                Dd: Dedup<Self::Output>,
+            {
+                let ($($T),*) = self;
+                $(let mut $T = $T.into_reader();)*
+
+                LocalEventual::spawn_stage_with_dedup("eventuals::join", Arc::new(dedup), move |mut writer| async move {
+                    // In the first section we wait until all values are available
+                    let mut len: usize = 0;
+                    let mut count: usize = 0;
+                    $(let mut $t = None; len += 1;)*
+                    let ($(mut $t,)*) = loop {
+                        select! {
+                            $(
+                                next = $T.next_snapshot_or_close(&writer) => {
+                                    if $t.replace(next?).is_none() {
+                                        count += 1;
+                                    }
+                                }
+                            )*
+                        }
+                        if count == len {
+                            break ($($t.unwrap()),*);
+                        }
+                    };
+                    // Once all values are available, start writing but continue
+                    // to update. The output is only as fresh as the oldest
+                    // input, so that is the timestamp which is carried.
+                    loop {
+                        let written_at = [$($t.written_at),*].iter().min().copied().unwrap();
+                        writer.write_at(($((*$t.value).clone(),)*), written_at);
+
+                        select! {
+                            $(
+                                next = $T.next_snapshot_or_close(&writer) => {
+                                    $t = next?;
+                                }
+                            )*
+                        }
+                    }
+                })
+            }
+        }
+    };
+}
+
+macro_rules! impl_tuples {
+    ($len:expr, $A:ident, $a:ident) => { };
+    ($len:expr, $A:ident, $a:ident, $($T:ident, $t:ident),+) => {
+        impl_tuple!($len, $A, $a, $($T, $t),+);
+        impl_tuples!($len - 1, $($T, $t),+);
+    }
+}
+
+impl_tuples!(12, A, a, B, b, C, c, D, d, E, e, F, f, G, g, H, h, I, i, J, j, K, k, L, l);
+
+/// An eventual that will only progress once all inputs are available, and then
+/// also progress with each change as they become available.
+/// See also `eventuals::join`.
+pub fn join<J>(joinable: J) -> LocalEventual<J::Output>
+where
+    J: LocalJoinable,
+    J::Output: PartialEq,
+{
+    joinable.join()
+}
+
+/// Like `join`, but with a specific dedup strategy for the output.
+/// See also `eventuals::join_with_dedup`.
+pub fn join_with_dedup<J, D>(joinable: J, dedup: D) -> LocalEventual<J::Output>
+where
+    J: LocalJoinable,
+    D: Dedup<J::Output>,
+{
+    joinable.join_with_dedup(dedup)
+}
+
+/// Prevents observation of values more frequently than the provided duration.
+/// The final value is guaranteed to be observed. See also `eventuals::throttle`.
+pub fn throttle<E>(read: E, duration: Duration) -> LocalEventual<E::Output>
+where
+    E: LocalIntoReader,
+{
+    let mut read = read.into_reader();
+    let dedup = read.dedup();
+    let timer = default_timer();
+
+    LocalEventual::spawn_stage_with_dedup(
+        "eventuals::throttle",
+        dedup,
+        move |mut writer| async move {
+            loop {
+                let mut next = read.next_snapshot_or_close(&writer).await?;
+                let end = timer.now() + duration;
+                loop {
+                    select! {
+                        n = read.next_snapshot_or_close(&writer) => {
+                            next = n?;
+                            #[cfg(feature = "tracing")]
+                            tracing::trace!(target: "eventuals", "coalesced value");
+                        }
+                        _ = timer.sleep_until(end) => {
+                            break;
+                        }
+                    }
+                }
+                let written_at = next.written_at;
+                writer.write_at(next.into_value(), written_at);
+            }
+        },
+    )
+}
+
+/// Produce a side effect with the latest snapshots as they become available.
+/// The caller must not drop the returned PipeHandle until it is no longer
+/// desirable to produce the side effect. See also `eventuals::pipe`.
+pub fn pipe<E, F>(reader: E, mut f: F) -> PipeHandle
+where
+    E: LocalIntoReader,
+    F: 'static + FnMut(E::Output),
+{
+    let mut reader = reader.into_reader();
+
+    // The Eventual<Never> is Send even though the task which holds the writer
+    // is not, so the same PipeHandle can be used for both.
+    let (writer, eventual) = Eventual::<Never>::new();
+    spawn_local("eventuals::pipe", writer, |writer| async move {
+        loop {
+            let value = reader.next_or_close(&writer).await?;
+            if let Err(panic) = catch_panic(|| async { f(value) }).await {
+                writer.handle_panic(panic, true)?;
+            }
+        }
+    });
+    PipeHandle::new(eventual)
+}
@@ -1,30 +1,55 @@
-use crate::*;
+use crate::{
+    dedup::{ByEq, Dedup, NoDedup},
+    error::catch_panic,
+    runtime::{self, default_timer},
+    *,
+};
 use futures::future::select_all;
 use never::Never;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::{future::Future, time::Instant};
-use tokio::{
-    self, select,
-    time::{sleep, sleep_until},
-};
+use tokio::select;
+
+#[cfg(feature = "tokio-runtime")]
+pub mod local;
 
 /// Applies an operation to each observed snapshot from the source. For example:
 /// map([1, 2, 3, 4, 5], |v| v+1) may produce something like [2, 6] or [3, 4,
 /// 6]. In this case, 6 is the only value guaranteed to be observed eventually.
-pub fn map<E, I, O, F, Fut>(source: E, mut f: F) -> Eventual<O>
+pub fn map<E, I, O, F, Fut>(source: E, f: F) -> Eventual<O>
 where
     E: IntoReader<Output = I>,
     F: 'static + Send + FnMut(I) -> Fut,
     I: Value,
+    O: Value + PartialEq,
+    Fut: Send + Future<Output = O>,
+{
+    map_with_dedup(source, ByEq, f)
+}
+
+/// Like `map`, but with a specific dedup strategy for the output. For example,
+/// `NoDedup` or `ByPtr` when the output is expensive to compare. See also
+/// `Eventual::with_dedup`.
+pub fn map_with_dedup<E, I, O, D, F, Fut>(source: E, dedup: D, mut f: F) -> Eventual<O>
+where
+    E: IntoReader<Output = I>,
+    D: Dedup<O>,
+    F: 'static + Send + FnMut(I) -> Fut,
+    I: Value,
     O: Value,
     Fut: Send + Future<Output = O>,
 {
     let mut source = source.into_reader();
 
-    Eventual::spawn(|mut writer| async move {
+    Eventual::spawn_stage_with_dedup("eventuals::map", Arc::new(dedup), |mut writer| async move {
         loop {
-            writer.write(f(source.next().await?).await);
+            let next = source.next_snapshot_or_close(&writer).await?;
+            let written_at = next.written_at;
+            match catch_panic(|| f(next.into_value())).await {
+                Ok(value) => writer.write_at(value, written_at),
+                Err(panic) => writer.handle_panic(panic, true)?,
+            }
         }
     })
 }
@@ -33,10 +58,16 @@ where
 /// about frequency or the value written except that at least "interval" time
 /// has passed since producing the last snapshot.
 pub fn timer(interval: Duration) -> Eventual<Instant> {
-    Eventual::spawn(move |mut writer| async move {
+    timer_on(default_timer(), interval)
+}
+
+/// Like `timer`, but with a specific Timer, which also provides the time
+/// written.
+pub fn timer_on(timer: Arc<dyn Timer>, interval: Duration) -> Eventual<Instant> {
+    Eventual::spawn_stage("eventuals::timer", move |mut writer| async move {
         loop {
-            writer.write(Instant::now());
-            sleep(interval).await;
+            writer.write(timer.now());
+            timer.sleep(interval).await;
         }
     })
 }
@@ -45,7 +76,19 @@ pub fn timer(interval: Duration) -> Eventual<Instant> {
 /// be used directly.
 pub trait Joinable {
     type Output;
-    fn join(self) -> Eventual<Self::Output>;
+
+    fn join(self) -> Eventual<Self::Output>
+    where
+        Self: Sized,
+        Self::Output: PartialEq,
+    {
+        self.join_with_dedup(ByEq)
+    }
+
+    /// Like `join`, but with a specific dedup strategy for the output.
+    fn join_with_dedup<D>(self, dedup: D) -> Eventual<Self::Output>
+    where
+        D: Dedup<Self::Output>;
 }
 
 macro_rules! impl_tuple {
@@ -71,11 +114,14 @@ macro_rules! impl_tuple {
             type Output = ($($T::Output),*);
 
             #[allow(non_snake_case)]
-            fn join(self) -> Eventual<Self::Output> {
+            fn join_with_dedup<Dd>(self, dedup: Dd) -> Eventual<Self::Output>
+            where
+                Dd: Dedup<Self::Output>,
+            {
                 let ($($T),*) = self;
                 $(let mut $T = $T.into_reader();)*
 
-                Eventual::spawn(move |mut writer| async move {
+                Eventual::spawn_stage_with_dedup("eventuals::join", Arc::new(dedup), move |mut writer| async move {
                     // In the first section we wait until all values are available
                     let mut len: usize = 0;
                     let mut count: usize = 0;
@@ -83,7 +129,7 @@ macro_rules! impl_tuple {
                     let ($(mut $t,)*) = loop {
                         select! {
                             $(
-                                next = $T.next() => {
+                                next = $T.next_snapshot_or_close(&writer) => {
                                     if $t.replace(next?).is_none() {
                                         count += 1;
                                     }
@@ -95,13 +141,15 @@ macro_rules! impl_tuple {
                         }
                     };
                     // Once all values are available, start writing but continue
-                    // to update.
+                    // to update. The output is only as fresh as the oldest
+                    // input, so that is the timestamp which is carried.
                     loop {
-                        writer.write(($($t.clone(),)*));
+                        let written_at = [$($t.written_at),*].iter().min().copied().unwrap();
+                        writer.write_at(($((*$t.value).clone(),)*), written_at);
 
                         select! {
                             $(
-                                next = $T.next() => {
+                                next = $T.next_snapshot_or_close(&writer) => {
                                     $t = next?;
                                 }
                             )*
@@ -133,10 +181,21 @@ impl_tuples!(12, A, a, B, b, C, c, D, d, E, e, F, f, G, g, H, h, I, i, J, j, K,
 pub fn join<J>(joinable: J) -> Eventual<J::Output>
 where
     J: Joinable,
+    J::Output: PartialEq,
 {
     joinable.join()
 }
 
+/// Like `join`, but with a specific dedup strategy for the output. This is
+/// useful when the joined values are expensive to compare.
+pub fn join_with_dedup<J, D>(joinable: J, dedup: D) -> Eventual<J::Output>
+where
+    J: Joinable,
+    D: Dedup<J::Output>,
+{
+    joinable.join_with_dedup(dedup)
+}
+
 pub trait Selectable {
     type Output;
     #[deprecated = "Not deterministic. This doesn't seem as harmful as filter, because it doesn't appear to miss updates."]
@@ -161,12 +220,18 @@ where
         // TODO: With specialization we can avoid what is essentially an
         // unnecessary clone when R is EventualReader
         let mut readers: Vec<_> = self.into_iter().map(|v| v.into_reader()).collect();
-        Eventual::spawn(move |mut writer| async move {
+        // The values are passed through, so they are deduped as the sources
+        // would dedup them.
+        let dedup = match readers.first() {
+            Some(reader) => reader.dedup(),
+            None => Arc::new(NoDedup),
+        };
+        Eventual::spawn_stage_with_dedup("eventuals::select", dedup, move |mut writer| async move {
             loop {
-                if readers.len() == 0 {
+                if readers.is_empty() {
                     return Err(Closed);
                 }
-                let read_futs: Vec<_> = readers.iter_mut().map(|r| r.next()).collect();
+                let read_futs: Vec<_> = readers.iter_mut().map(|r| r.next_timestamped()).collect();
 
                 let (output, index, remainder) = select_all(read_futs).await;
 
@@ -175,11 +240,14 @@ where
                 drop(remainder);
 
                 match output {
-                    Ok(value) => {
-                        writer.write(value);
+                    Ok((value, written_at)) => {
+                        writer.write_at(value, written_at);
                     }
                     Err(Closed) => {
-                        readers.remove(index);
+                        let reader = readers.remove(index);
+                        if readers.is_empty() {
+                            writer.close_after(&reader);
+                        }
                     }
                 }
             }
@@ -190,34 +258,176 @@ where
 /// Prevents observation of values more frequently than the provided duration.
 /// The final value is guaranteed to be observed.
 pub fn throttle<E>(read: E, duration: Duration) -> Eventual<E::Output>
+where
+    E: IntoReader,
+{
+    throttle_on(default_timer(), read, duration)
+}
+
+/// Like `throttle`, but with a specific Timer.
+pub fn throttle_on<E>(timer: Arc<dyn Timer>, read: E, duration: Duration) -> Eventual<E::Output>
 where
     E: IntoReader,
 {
     let mut read = read.into_reader();
+    let dedup = read.dedup();
 
-    Eventual::spawn(move |mut writer| async move {
+    Eventual::spawn_stage_with_dedup("eventuals::throttle", dedup, move |mut writer| async move {
         loop {
-            let mut next = read.next().await?;
-            let end = tokio::time::Instant::now() + duration;
+            let mut next = read.next_snapshot_or_close(&writer).await?;
+            let end = timer.now() + duration;
             loop {
                 // Allow replacing the value until the time is up. This
                 // necessarily introduces latency but de-duplicates when there
                 // are intermittent bursts. Not sure what is better. Matching
                 // common-ts for now.
                 select! {
-                    n = read.next() => {
+                    n = read.next_snapshot_or_close(&writer) => {
                         next = n?;
+                        #[cfg(feature = "tracing")]
+                        tracing::trace!(target: "eventuals", "coalesced value");
                     }
-                    _ = sleep_until(end) => {
+                    _ = timer.sleep_until(end) => {
                         break;
                     }
                 }
             }
-            writer.write(next);
+            let written_at = next.written_at;
+            writer.write_at(next.into_value(), written_at);
+        }
+    })
+}
+
+/// What `expire_after` does when the source has not been written for longer
+/// than the time to live.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OnExpire {
+    /// Return to having no value, as with `EventualWriter::reset`, until the
+    /// source is written again.
+    ///
+    /// **The reset is not observed by readers.** Readers can only observe
+    /// values, so nothing is woken by it. Only `Eventual::value_immediate`
+    /// and `Eventual::value` on this Eventual itself see the expiry. Stages
+    /// downstream of it, such as `map`, `join` or `pipe`, keep the last value
+    /// they produced. Use `Close` to stop a pipeline from serving a stale
+    /// value, or `liveness` to react to the source stalling.
+    Reset,
+    /// Close with `ClosedReason::Expired`.
+    Close,
+}
+
+/// Mirrors the source, but expires the value if the source is not written for
+/// longer than `ttl`. Every write counts, even if it writes a value equal to
+/// the previous one. The age of a value is judged from the time at which it
+/// was written (see `EventualReader::next_timestamped`), so the output of a
+/// stage downstream of a stalled source expires too. See `OnExpire::Reset`
+/// for why an expiry only reaches the stages after this one with
+/// `OnExpire::Close`.
+pub fn expire_after<E>(source: E, ttl: Duration, on_expire: OnExpire) -> Eventual<E::Output>
+where
+    E: IntoReader,
+{
+    expire_after_on(default_timer(), source, ttl, on_expire)
+}
+
+/// Like `expire_after`, but with a specific Timer.
+pub fn expire_after_on<E>(
+    timer: Arc<dyn Timer>,
+    source: E,
+    ttl: Duration,
+    on_expire: OnExpire,
+) -> Eventual<E::Output>
+where
+    E: IntoReader,
+{
+    let mut source = source.into_reader();
+    let dedup = source.dedup();
+
+    Eventual::spawn_stage_with_dedup(
+        "eventuals::expire_after",
+        dedup,
+        move |mut writer| async move {
+            // None while there is no value to expire.
+            let mut deadline = None;
+            loop {
+                let next = match deadline {
+                    None => source.next_write().await,
+                    Some(at) => select! {
+                        next = source.next_write() => next,
+                        _ = timer.sleep_until(at) => {
+                            match on_expire {
+                                OnExpire::Reset => writer.reset(),
+                                OnExpire::Close => {
+                                    writer.close_with_reason(ClosedReason::Expired);
+                                    return Err(Closed);
+                                }
+                            }
+                            deadline = None;
+                            continue;
+                        }
+                    },
+                };
+                let next = next.map_err(|Closed| {
+                    writer.close_after(&source);
+                    Closed
+                })?;
+                deadline = Some(expires_at(&*timer, next.written_at, ttl));
+                let written_at = next.written_at;
+                writer.write_at(next.into_value(), written_at);
+            }
+        },
+    )
+}
+
+/// True while the source has been written within the last `ttl`. This is
+/// useful for wiring health checks into a pipeline. Every write counts, even
+/// if it writes a value equal to the previous one. Once the source is closed,
+/// false is written before closing.
+pub fn liveness<E>(source: E, ttl: Duration) -> Eventual<bool>
+where
+    E: IntoReader,
+{
+    liveness_on(default_timer(), source, ttl)
+}
+
+/// Like `liveness`, but with a specific Timer.
+pub fn liveness_on<E>(timer: Arc<dyn Timer>, source: E, ttl: Duration) -> Eventual<bool>
+where
+    E: IntoReader,
+{
+    let mut source = source.into_reader();
+
+    Eventual::spawn_stage("eventuals::liveness", move |mut writer| async move {
+        let mut deadline: Option<Instant> = None;
+        loop {
+            let alive = deadline.is_some_and(|deadline| deadline > timer.now());
+            writer.write(alive);
+            let next = match deadline {
+                Some(at) if alive => select! {
+                    next = source.next_write() => next,
+                    _ = timer.sleep_until(at) => continue,
+                },
+                _ => source.next_write().await,
+            };
+            match next {
+                Ok(next) => deadline = Some(expires_at(&*timer, next.written_at, ttl)),
+                Err(Closed) => {
+                    writer.write(false);
+                    writer.close_after(&source);
+                    return Err(Closed);
+                }
+            }
         }
     })
 }
 
+// Values are stamped with the system clock when written, whereas deadlines
+// must be on the Timer's clock. So, only the age of the value is taken from
+// the system clock.
+fn expires_at(timer: &dyn Timer, written_at: Instant, ttl: Duration) -> Instant {
+    timer.now() + ttl.saturating_sub(written_at.elapsed())
+}
+
 /// Produce a side effect with the latest snapshots as they become available.
 /// The caller must not drop the returned PipeHandle until it is no longer
 /// desirable to produce the side effect.
@@ -228,18 +438,17 @@ where
 {
     let mut reader = reader.into_reader();
 
-    #[allow(unreachable_code)]
-    PipeHandle::new(Eventual::spawn(|_writer| async move {
-        loop {
-            f(reader.next().await?);
-        }
-        // Prevent the writer from being dropped. Normally we would expect
-        // _writer to not be dropped, but the async move creates a new lexical
-        // scope for the Future. If _writer is not moved into the Future it
-        // would be dropped right after the Future is created and before the
-        // closure returns. Without this line, Pipe stops prematurely.
-        drop(_writer);
-    }))
+    PipeHandle::new(Eventual::spawn_stage(
+        "eventuals::pipe",
+        |writer| async move {
+            loop {
+                let value = reader.next_or_close(&writer).await?;
+                if let Err(panic) = catch_panic(|| async { f(value) }).await {
+                    writer.handle_panic(panic, true)?;
+                }
+            }
+        },
+    ))
 }
 
 /// Similar to `pipe`, but allows for the side effect to be async.
@@ -252,13 +461,17 @@ where
 {
     let mut reader = reader.into_reader();
 
-    #[allow(unreachable_code)]
-    PipeHandle::new(Eventual::spawn(|_writer| async move {
-        loop {
-            f(reader.next().await?).await;
-        }
-        drop(_writer);
-    }))
+    PipeHandle::new(Eventual::spawn_stage(
+        "eventuals::pipe_async",
+        |writer| async move {
+            loop {
+                let value = reader.next_or_close(&writer).await?;
+                if let Err(panic) = catch_panic(|| f(value)).await {
+                    writer.handle_panic(panic, true)?;
+                }
+            }
+        },
+    ))
 }
 
 /// Pipe ceases when this is dropped
@@ -268,16 +481,47 @@ pub struct PipeHandle {
 }
 
 impl PipeHandle {
-    fn new(eventual: Eventual<Never>) -> Self {
+    pub(crate) fn new(eventual: Eventual<Never>) -> Self {
         Self { inner: eventual }
     }
 
+    /// Set what the pipe does when the side effect panics. See also
+    /// `Eventual::with_panic_policy`.
+    pub fn with_panic_policy(self, policy: PanicPolicy) -> Self {
+        Self {
+            inner: self.inner.with_panic_policy(policy),
+        }
+    }
+
+    /// Name the pipe for its tracing spans and events. See also
+    /// `Eventual::named`.
+    pub fn named(self, name: &str) -> Self {
+        Self {
+            inner: self.inner.named(name),
+        }
+    }
+
+    /// Why the pipe stopped, or None if it is still running. For example,
+    /// `ClosedReason::Panicked` if the side effect panicked.
+    pub fn closed_reason(&self) -> Option<ClosedReason> {
+        self.inner.closed_reason()
+    }
+
     /// Prevent the pipe operation from ever stopping for as long
     /// as snapshots are observed.
     #[inline]
     pub fn forever(self) {
+        self.forever_on(&runtime::DefaultSpawner)
+    }
+
+    /// Like `forever`, but runs the task which keeps the pipe alive with a
+    /// specific Spawner.
+    pub fn forever_on<S>(self, spawner: &S)
+    where
+        S: Spawner + ?Sized,
+    {
         let Self { inner } = self;
-        tokio::task::spawn(async move {
+        runtime::spawn_on(spawner, async move {
             // Drops the reader when the writer is closed
             // This value is always Err(Closed) because inner is Eventual<Never>
             let _closed = inner.value().await;
@@ -290,15 +534,16 @@ pub fn handle_errors<E, F, Ok, Err>(source: E, mut f: F) -> Eventual<Ok>
 where
     E: IntoReader<Output = Result<Ok, Err>>,
     F: 'static + Send + FnMut(Err),
-    Ok: Value,
+    Ok: Value + PartialEq,
     Err: Value,
 {
     let mut reader = source.into_reader();
 
-    Eventual::spawn(move |mut writer| async move {
+    Eventual::spawn_stage("eventuals::handle_errors", move |mut writer| async move {
         loop {
-            match reader.next().await? {
-                Ok(v) => writer.write(v),
+            let (next, written_at) = reader.next_timestamped_or_close(&writer).await?;
+            match next {
+                Ok(v) => writer.write_at(v, written_at),
                 Err(e) => f(e),
             }
         }
@@ -323,31 +568,32 @@ where
 // for usage.
 pub fn retry<Ok, Err, F, Fut>(mut f: F) -> Eventual<Ok>
 where
-    Ok: Value,
+    Ok: Value + PartialEq,
     Err: Value,
     Fut: Send + Future<Output = Eventual<Result<Ok, Err>>>,
     F: 'static + Send + FnMut(Option<Err>) -> Fut,
 {
-    Eventual::spawn(move |mut writer| async move {
-        loop {
-            let mut e = f(None).await.subscribe();
-            let mut next = e.next().await;
+    Eventual::spawn_stage("eventuals::retry", move |mut writer| async move {
+        let mut e = f(None).await.subscribe();
+        let mut next = e.next_timestamped_or_close(&writer).await;
 
-            loop {
-                match next? {
-                    Ok(v) => {
-                        writer.write(v);
-                        next = e.next().await;
-                    }
-                    Err(err) => {
-                        select! {
-                            e_temp = f(Some(err)) => {
-                                e = e_temp.subscribe();
-                                next = e.next().await;
-                            }
-                            n_temp = e.next() => {
-                                next = n_temp;
-                            }
+        loop {
+            let (next_value, written_at) = next?;
+            match next_value {
+                Ok(v) => {
+                    writer.write_at(v, written_at);
+                    next = e.next_timestamped_or_close(&writer).await;
+                }
+                Err(err) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(target: "eventuals", "retrying");
+                    select! {
+                        e_temp = f(Some(err)) => {
+                            e = e_temp.subscribe();
+                            next = e.next_timestamped_or_close(&writer).await;
+                        }
+                        n_temp = e.next_timestamped_or_close(&writer) => {
+                            next = n_temp;
                         }
                     }
                 }
@@ -370,7 +616,7 @@ where
     R: IntoReader,
     F: 'static + Send + FnMut(R::Output) -> Fut,
     E: 'static + Send + Sync + FnMut(Err) -> FutE,
-    Ok: Value,
+    Ok: Value + PartialEq,
     Err: Value,
     Fut: Send + Future<Output = Result<Ok, Err>>,
     FutE: Send + Future<Output = ()>,
@@ -416,7 +662,8 @@ where
                 // stuck here on the last value forever. (Unless the readers are dropped)
                 reader.force_dirty();
             }
-            map(reader, move |value| {
+            // The output of retry is deduped, so the results need not be.
+            map_with_dedup(reader, NoDedup, move |value| {
                 let fut = {
                     let mut locked = f.lock().unwrap();
                     locked(value)
@@ -433,10 +680,12 @@ where
     R: IntoReader,
 {
     let mut source = source.into_reader();
-    Eventual::spawn(|mut writer| async move {
+    let dedup = source.dedup();
+    Eventual::spawn_stage_with_dedup("eventuals::init_with", dedup, |mut writer| async move {
         writer.write(value);
         loop {
-            writer.write(source.next().await?);
+            let (value, written_at) = source.next_timestamped_or_close(&writer).await?;
+            writer.write_at(value, written_at);
         }
     })
 }
@@ -450,25 +699,27 @@ where
 {
     let mut source_1 = source_1.into_reader();
     let mut source_2 = source_2.into_reader();
+    let dedup = source_1.dedup();
 
-    Eventual::spawn(|mut writer| async move {
+    Eventual::spawn_stage_with_dedup("eventuals::prefer", dedup, |mut writer| async move {
         loop {
             select! {
                 biased;
 
-                one = source_1.next() => {
-                    if let Ok(one) = one {
-                        writer.write(one);
+                one = source_1.next_timestamped() => {
+                    if let Ok((one, written_at)) = one {
+                        writer.write_at(one, written_at);
                         break;
                     } else {
                         loop {
-                            writer.write(source_2.next().await?);
+                            let (value, written_at) = source_2.next_timestamped_or_close(&writer).await?;
+                            writer.write_at(value, written_at);
                         }
                     }
                 }
-                two = source_2.next() => {
-                    if let Ok(two) = two {
-                        writer.write(two);
+                two = source_2.next_timestamped() => {
+                    if let Ok((two, written_at)) = two {
+                        writer.write_at(two, written_at);
                     } else {
                         break;
                     }
@@ -477,7 +728,8 @@ where
         }
         drop(source_2);
         loop {
-            writer.write(source_1.next().await?);
+            let (value, written_at) = source_1.next_timestamped_or_close(&writer).await?;
+            writer.write_at(value, written_at);
         }
     })
 }
@@ -503,13 +755,14 @@ where
     R1: IntoReader<Output = R2>,
     R2: IntoReader,
     R2: Value,
+    R2::Output: PartialEq,
 {
     let mut outer = outer.into_reader();
-    Eventual::spawn(|mut writer| async move {
+    Eventual::spawn_stage("eventuals::flatten", |mut writer| async move {
         // Always need to get the first outer eventual. If there
         // is none, then there are no values and this can return because
         // there is never anything else to write.
-        let mut inner = outer.next().await?.into_reader();
+        let mut inner = outer.next_or_close(&writer).await?.into_reader();
         loop {
             select! {
                 next = outer.next() => {
@@ -520,18 +773,19 @@ where
                         // If we get here it means there will never be any more
                         // sources. Exhaust the current one, then break.
                         loop {
-                            writer.write(inner.next().await?);
+                            let (value, written_at) = inner.next_timestamped_or_close(&writer).await?;
+                            writer.write_at(value, written_at);
                         }
                     }
                 }
-                next = inner.next() => {
+                next = inner.next_timestamped() => {
                     // If we get a new value, write it.
-                    if let Ok(next) = next {
-                        writer.write(next);
+                    if let Ok((next, written_at)) = next {
+                        writer.write_at(next, written_at);
                     } else {
                         // If the current source runs out of values, always
                         // try to move on to the next source.
-                        inner = outer.next().await?.into_reader();
+                        inner = outer.next_or_close(&writer).await?.into_reader();
                     }
                 }
             }
@@ -1,2 +1,152 @@
+use futures::future::poll_fn;
+use futures::FutureExt;
+use std::{
+    any::Any,
+    error::Error,
+    future::Future,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    task::Poll,
+};
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Closed;
+
+/// Why an Eventual was closed. Readers only ever observe `Closed`, but the
+/// reason may be retrieved with `closed_reason` on the Eventual or reader.
+/// Combinators close their output with the reason their source was closed,
+/// so the reason propagates through a pipeline.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum ClosedReason {
+    /// The writer closed or finalized the Eventual, or was dropped.
+    WriterDropped,
+    /// The source of a combinator was closed by its writer.
+    UpstreamClosed,
+    /// The closure producing values for the Eventual panicked with the
+    /// contained message.
+    Panicked(String),
+    /// The task producing values for the Eventual was dropped before it
+    /// completed, such as when the runtime shuts down.
+    Cancelled,
+    /// The source of `expire_after` was not written for longer than the
+    /// time to live.
+    Expired,
+    /// The writer closed the Eventual with an error. See also
+    /// `EventualWriter::close_with_error`.
+    Error(Arc<dyn Error + Send + Sync>),
+}
+
+impl ClosedReason {
+    /// The reason to close a combinator with when its source closed for
+    /// this reason.
+    pub(crate) fn downstream(self) -> Self {
+        match self {
+            Self::WriterDropped => Self::UpstreamClosed,
+            reason => reason,
+        }
+    }
+}
+
+// Errors are compared by identity, since dyn Error is not PartialEq.
+impl PartialEq for ClosedReason {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::WriterDropped, Self::WriterDropped) => true,
+            (Self::UpstreamClosed, Self::UpstreamClosed) => true,
+            (Self::Panicked(a), Self::Panicked(b)) => a == b,
+            (Self::Cancelled, Self::Cancelled) => true,
+            (Self::Expired, Self::Expired) => true,
+            (Self::Error(a), Self::Error(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ClosedReason {}
+
+/// What to do when a closure passed to `Eventual::spawn` or to a combinator
+/// such as `map` or `pipe` panics. The Eventual it was writing to observes
+/// `ClosedReason::Panicked` unless the policy is `Skip`. The policy is set per
+/// Eventual with `Eventual::with_panic_policy`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Close the Eventual, then continue unwinding the task so that the
+    /// executor sees the panic.
+    Propagate,
+    /// Close the Eventual. This is the default.
+    #[default]
+    Close,
+    /// Skip the value which caused the panic and continue with the next one
+    /// from the source. The stage is not restarted, so any state held by the
+    /// closure is kept. Tasks passed to `Eventual::spawn` do not have a source
+    /// to continue from, so they are closed as with `Close`. See
+    /// `Eventual::spawn_supervised` to restart those instead.
+    Skip,
+}
+
+impl PanicPolicy {
+    pub(crate) fn from_u8(policy: u8) -> Self {
+        match policy {
+            0 => Self::Propagate,
+            1 => Self::Close,
+            _ => Self::Skip,
+        }
+    }
+}
+
+pub(crate) type Panic = Box<dyn Any + Send>;
+
+/// Call `f` and wait for the future it returns, catching a panic from either.
+pub(crate) async fn catch_panic<F, Fut>(f: F) -> Result<Fut::Output, Panic>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future,
+{
+    AssertUnwindSafe(async move { f().await })
+        .catch_unwind()
+        .await
+}
+
+/// Like `catch_panic`, but calls `on_panic` before the future is dropped. So,
+/// a panic can be reported before anything owned by the future (such as its
+/// writer) is dropped.
+pub(crate) async fn catch_panic_with<F, Fut>(
+    f: F,
+    on_panic: impl FnOnce(Panic),
+) -> Option<Fut::Output>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future,
+{
+    let future = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(future) => future,
+        Err(panic) => {
+            on_panic(panic);
+            return None;
+        }
+    };
+    let mut future = Box::pin(future);
+    let output = poll_fn(|cx| {
+        panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx)))
+            .map_or_else(|panic| Poll::Ready(Err(panic)), |poll| poll.map(Ok))
+    })
+    .await;
+    match output {
+        Ok(output) => Some(output),
+        Err(panic) => {
+            on_panic(panic);
+            None
+        }
+    }
+}
+
+pub(crate) fn panic_message(panic: &Panic) -> String {
+    if let Some(message) = panic.downcast_ref::<&'static str>() {
+        message.to_string()
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
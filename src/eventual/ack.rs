@@ -0,0 +1,134 @@
+use futures::channel::oneshot::{self, Receiver, Sender};
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc, Mutex, Weak,
+    },
+};
+
+// Write acknowledgements piggyback on the subscribers of an eventual. An
+// acknowledged write attaches an Ack to every subscriber it notifies. Each
+// subscriber holds on to it until it waits for a new value again, and a task
+// which observed the write (such as a map) attaches it to its own writes in
+// the meantime. So, the Ack follows the value through the pipeline, and the
+// write is acknowledged once the last clone is dropped.
+#[derive(Clone)]
+pub(crate) struct Ack(#[allow(dead_code)] Arc<AckInner>);
+
+struct AckInner {
+    _sender: Sender<()>,
+}
+
+// The number of Acks alive in the process. While it is zero there is nothing
+// to carry, so tasks need not track the subscribers they observe.
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+impl Ack {
+    /// The receiver is cancelled once every clone of the Ack is dropped.
+    pub fn new() -> (Self, Receiver<()>) {
+        let (sender, receiver) = oneshot::channel();
+        PENDING.fetch_add(1, SeqCst);
+        (Self(Arc::new(AckInner { _sender: sender })), receiver)
+    }
+}
+
+impl Drop for AckInner {
+    fn drop(&mut self) {
+        PENDING.fetch_sub(1, SeqCst);
+    }
+}
+
+/// True if any acknowledged write has yet to be acknowledged.
+pub(crate) fn pending() -> bool {
+    PENDING.load(SeqCst) != 0
+}
+
+/// The Acks held by a subscriber. See also `Change::notify`.
+#[derive(Default)]
+pub(crate) struct HeldAcks(Mutex<Vec<Ack>>);
+
+impl HeldAcks {
+    pub fn extend(&self, acks: &[Ack]) {
+        self.0.lock().unwrap().extend_from_slice(acks);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+
+    /// Drop the held Acks, since the subscriber is waiting for a new value.
+    pub fn release(&self) {
+        let released = std::mem::take(&mut *self.0.lock().unwrap());
+        // Dropped outside of the lock, since this may complete a write_acked.
+        drop(released);
+    }
+}
+
+// The subscribers of a task which have observed an acknowledged write. The
+// Acks themselves stay with the subscribers, so that they are released
+// wherever the subscriber waits or is dropped.
+#[derive(Clone, Default)]
+pub(crate) struct TaskAcks(Arc<Mutex<Vec<Weak<HeldAcks>>>>);
+
+thread_local! {
+    static CURRENT: RefCell<Option<TaskAcks>> = const { RefCell::new(None) };
+}
+
+impl TaskAcks {
+    /// Make these the Acks of the task being polled on this thread until the
+    /// returned guard is dropped.
+    pub fn enter(&self) -> TaskAcksGuard {
+        let prev = CURRENT.with(|current| current.replace(Some(self.clone())));
+        TaskAcksGuard { prev }
+    }
+}
+
+pub(crate) struct TaskAcksGuard {
+    prev: Option<TaskAcks>,
+}
+
+impl Drop for TaskAcksGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CURRENT.with(|current| *current.borrow_mut() = prev);
+    }
+}
+
+/// Called when a subscriber holding Acks observes a value, so that the
+/// task's writes carry them until the subscriber waits again. Returns false
+/// if no task is tracking its subscribers on this thread.
+pub(crate) fn carry(held: &Arc<HeldAcks>) -> bool {
+    CURRENT.with(|current| match current.borrow().as_ref() {
+        Some(task) => {
+            let mut subscribers = task.0.lock().unwrap();
+            let held = Arc::downgrade(held);
+            if !subscribers.iter().any(|s| s.ptr_eq(&held)) {
+                subscribers.push(held);
+            }
+            true
+        }
+        None => false,
+    })
+}
+
+/// The Acks to attach to a write made by the current task.
+pub(crate) fn carried() -> Vec<Ack> {
+    if !pending() {
+        return Vec::new();
+    }
+    CURRENT.with(|current| {
+        let current = current.borrow();
+        let Some(task) = current.as_ref() else {
+            return Vec::new();
+        };
+        let mut subscribers = task.0.lock().unwrap();
+        // Subscribers which have been dropped no longer hold anything.
+        subscribers.retain(|s| s.strong_count() != 0);
+        subscribers
+            .iter()
+            .filter_map(Weak::upgrade)
+            .flat_map(|held| held.0.lock().unwrap().clone())
+            .collect()
+    })
+}
@@ -0,0 +1,82 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::{Duration, Instant},
+};
+
+// Wakes a thread parked in block_on.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Drive a future to completion on the current thread by parking it between
+/// polls. Returns None if the timeout elapses first. This does not require
+/// any runtime, which makes it suitable for plain OS threads. It must not be
+/// used from an async context, because parking would stall the executor.
+#[track_caller]
+pub(crate) fn block_on<F>(future: F, timeout: Option<Duration>) -> Option<F::Output>
+where
+    F: Future,
+{
+    if is_async_context() {
+        panic!(
+            "Blocking eventuals APIs cannot be called from within an async context \
+            because they would block the executor. Await the async equivalent instead."
+        );
+    }
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    futures::pin_mut!(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return Some(output);
+        }
+        // Spurious wakeups are fine. They just result in another poll.
+        match deadline {
+            None => thread::park(),
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                thread::park_timeout(deadline - now);
+            }
+        }
+    }
+}
+
+// True if the thread is driving async tasks for a tokio runtime. The threads
+// of spawn_blocking are allowed to block. Tokio does not expose this check,
+// so it is made by using a blocking tokio API which performs it, like
+// blocking_recv does for tokio's own channels. Its panic is reported to the
+// panic hook before the one from block_on.
+#[cfg(feature = "tokio-runtime")]
+fn is_async_context() -> bool {
+    if tokio::runtime::Handle::try_current().is_err() {
+        return false;
+    }
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+    let _ = sender.send(());
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+        receiver.blocking_recv()
+    }))
+    .is_err()
+}
+
+#[cfg(not(feature = "tokio-runtime"))]
+fn is_async_context() -> bool {
+    false
+}
@@ -1,257 +1,300 @@
-use super::*;
+use super::{
+    ack::{self, Ack, HeldAcks},
+    dedup::Dedup,
+    idle::{Busy, IdleScope},
+    *,
+};
 
-use crate::Ptr;
 use std::{
-    collections::HashSet,
-    hash::{Hash, Hasher},
-    mem,
-    ops::DerefMut,
-    ptr,
     sync::{Arc, Mutex},
     task::Waker,
+    time::Instant,
 };
 
-#[cfg(feature = "trace")]
-mod busy {
-    use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
-    use tokio::sync::Notify;
-
-    pub static BUSY_COUNT: AtomicUsize = AtomicUsize::new(0);
-    pub static BUSY_WAKER: Notify = Notify::const_new();
+/// A snapshot tagged with the generation of the write that produced it.
+/// Versions are unique per eventual and increase with every write, so two
+/// snapshots with the same version are known to hold the same value without
+/// having to compare them.
+///
+/// The value is shared by the writer and every reader that observes it, so
+/// writes never clone the value.
+pub struct Versioned<T> {
+    pub value: Arc<T>,
+    pub version: u64,
+    // When the value was written. Combinators which derive their value from a
+    // source carry the source's timestamp instead.
+    pub written_at: Instant,
+}
 
-    pub fn set_busy() {
-        BUSY_COUNT.fetch_add(1, SeqCst);
-    }
-    pub fn clear_busy() {
-        let prev = BUSY_COUNT.fetch_sub(1, SeqCst);
-        debug_assert!(prev != 0);
-        if prev == 1 {
-            BUSY_WAKER.notify_waiters();
+impl<T> Clone for Versioned<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            version: self.version,
+            written_at: self.written_at,
         }
     }
+}
 
-    /// Ready once all eventuals readers are waiting on a new value. _Generally_
-    /// speaking, it is possible to ensure that a change has propagated through
-    /// an eventuals pipeline using this method. However, there is no guarantee
-    /// that this will complete in a timely fashion if ever. A sufficiently
-    /// layered pipeline that is always moving values through may never be idle.
-    /// So, this is only useful in isolated tests.
-    pub async fn idle() {
-        loop {
-            let notified = BUSY_WAKER.notified();
-            if BUSY_COUNT.load(SeqCst) == 0 {
-                return;
-            }
-            notified.await
-        }
+impl<T> Versioned<T>
+where
+    T: LocalValue,
+{
+    /// Clone the value out of the snapshot, unless this is the only
+    /// reference to it.
+    pub fn into_value(self) -> T {
+        Arc::try_unwrap(self.value).unwrap_or_else(|value| T::clone(&value))
+    }
+
+    /// True if observing `next` after `self` would be redundant. The version
+    /// check is the fast path. Values are only compared when versions differ,
+    /// because distinct writes may still write equal values.
+    pub fn is_same(&self, next: &Self, dedup: &dyn Dedup<T>) -> bool {
+        self.version == next.version || dedup.is_duplicate(&self.value, &next.value)
     }
 }
 
-#[cfg(feature = "trace")]
-pub use busy::idle;
+pub enum ChangeValNoWake<T> {
+    None,
+    Value(Versioned<T>),
+    Finalized(Option<Versioned<T>>),
+}
 
-struct Busy<T>(T);
+enum ChangeVal {
+    // Only held for its Drop, which clears the busy count.
+    #[allow(dead_code)]
+    Busy(Busy),
+    Waker(Waker),
+}
 
-impl<T> Busy<T> {
-    fn new(value: T) -> Self {
-        #[cfg(feature = "trace")]
-        busy::set_busy();
-        Self(value)
-    }
+/// The per-subscriber half of an eventual. Values are not pushed to
+/// subscribers. Instead the writer keeps the single latest snapshot in
+/// SharedState and a subscriber only holds the waker (if any) of the task
+/// which is waiting on it. So, a write is a wakeup per waiting subscriber,
+/// and each subscriber compares versions against its previous observation
+/// when polled.
+#[derive(Clone)]
+pub struct Change {
+    inner: Arc<Mutex<ChangeInner>>,
+    // Acknowledged writes which the subscriber has been notified of, held
+    // until it waits for a new value again. See also ack.rs
+    acks: Arc<HeldAcks>,
+    // The IdleScope (if any) which is told whenever the subscriber is busy.
+    scope: Option<IdleScope>,
+}
 
-    fn unbusy(mut self) -> T {
-        
-            let inner = unsafe {ptr::read(&mut self.0)};
-            mem::forget(self);
-            #[cfg(feature = "trace")]
-            busy::clear_busy();
+struct ChangeInner {
+    val: ChangeVal,
+    // Set when a value was put off because the task could not carry its Acks.
+    // See `Change::defer`.
+    deferred: bool,
+}
 
-            inner
-        
+impl Change {
+    pub fn new(scope: Option<IdleScope>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ChangeInner {
+                val: ChangeVal::Busy(Busy::new(scope.as_ref())),
+                deferred: false,
+            })),
+            acks: Arc::default(),
+            scope,
+        }
     }
-}
 
-impl<T> Deref for Busy<T> {
-    type Target = T;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    fn busy(&self) -> ChangeVal {
+        ChangeVal::Busy(Busy::new(self.scope.as_ref()))
     }
-}
 
-impl<T> Drop for Busy<T> {
-    fn drop(&mut self) {
-        #[cfg(feature = "trace")]
-        busy::clear_busy();
+    /// Take the waker, if the subscriber is waiting. The subscriber is busy
+    /// from the time it is woken until it waits again. The subscriber holds
+    /// on to `acks` until then, whether or not it was waiting.
+    pub fn notify(&self, acks: &[Ack]) -> Option<Waker> {
+        let mut inner = self.inner.lock().unwrap();
+        if !acks.is_empty() {
+            self.acks.extend(acks);
+        }
+        match &inner.val {
+            ChangeVal::Busy(_) => None,
+            ChangeVal::Waker(_) => match std::mem::replace(&mut inner.val, self.busy()) {
+                ChangeVal::Waker(waker) => Some(waker),
+                ChangeVal::Busy(_) => unreachable!(),
+            },
+        }
     }
-}
 
-enum ChangeVal<T> {
-    None(Busy<()>),
-    Value(Busy<T>),
-    Finalized(Busy<Option<T>>),
-    Waker(Waker),
-}
+    fn set_busy(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if let ChangeVal::Waker(_) = &inner.val {
+            inner.val = self.busy();
+        }
+    }
 
-pub enum ChangeValNoWake<T> {
-    None,
-    Value(T),
-    Finalized(Option<T>),
-}
+    fn set_waker(&self, waker: &Waker) {
+        self.inner.lock().unwrap().val = ChangeVal::Waker(waker.clone());
+        self.acks.release();
+    }
 
-pub struct Change<T> {
-    inner: Ptr<Mutex<ChangeVal<T>>>,
+    // Tasks only track the subscribers they observe while an Ack is pending,
+    // so a task polled just before an acknowledged write may observe the
+    // write without carrying its Acks. In that case the value is put off
+    // once, so that the task is polled again and tracks the subscriber.
+    // Readers outside of any task get the value on the second poll.
+    fn defer(&self, carried: bool) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        inner.deferred = !carried && !inner.deferred;
+        inner.deferred
+    }
 }
 
 pub struct ChangeReader<T> {
-    pub change: Change<T>,
+    pub change: Change,
+    // The key of change in the subscribers of unsubscribe_from.
+    pub key: usize,
     pub unsubscribe_from: Arc<SharedState<T>>,
 }
 
 impl<T> Drop for ChangeReader<T> {
     fn drop(&mut self) {
-        let mut lock = self.unsubscribe_from.subscribers.lock().unwrap();
-        let mut updated: HashSet<_> = lock.deref().deref().clone();
-        if updated.remove(&self.change) {
-            *lock = Arc::new(updated);
-        }
+        // The Acks held by the subscriber are released along with the Change.
+        self.unsubscribe_from
+            .subscribers
+            .lock()
+            .unwrap()
+            .remove(self.key);
+        self.unsubscribe_from.publish_subscriber_count();
     }
 }
 
-impl<T> Change<T>
+impl<T> ChangeReader<T>
 where
-    T: Value,
+    T: LocalValue,
 {
-    pub fn new() -> Self {
-        Self {
-            inner: Ptr::new(Mutex::new(ChangeVal::None(Busy::new(())))),
-        }
-    }
-
+    /// Poll for a snapshot which is different than `prev`, updating `prev`
+    /// with the result. If the eventual is finalized the final value (if any)
+    /// is returned once as though it were a normal value, followed by
+    /// Err(Closed) from then on.
     pub fn poll(
         &self,
-        cmp: &Option<Result<T, Closed>>,
+        prev: &mut Option<Result<Versioned<T>, Closed>>,
         cx: &mut Context,
-    ) -> Option<Result<T, Closed>> {
-        let mut lock = self.inner.lock().unwrap();
+    ) -> Poll<Result<Versioned<T>, Closed>> {
+        self.poll_with(prev, cx, Some(&*self.unsubscribe_from.dedup), true)
+    }
 
-        // Move the value out pre-emptively to keep things sane for the borrow checker.
-        // Depending on the branch ahead we'll swap in different values.
-        let value = mem::replace(lock.deref_mut(), ChangeVal::None(Busy::new(())));
+    /// Like `poll`, but for checking whether a value is available without
+    /// waiting on it. A value is never put off, since nothing would poll
+    /// again for it. See also `Change::defer`.
+    pub fn poll_now(
+        &self,
+        prev: &mut Option<Result<Versioned<T>, Closed>>,
+        cx: &mut Context,
+    ) -> Poll<Result<Versioned<T>, Closed>> {
+        self.poll_with(prev, cx, Some(&*self.unsubscribe_from.dedup), false)
+    }
 
-        match value {
-            // If there is a new value and it is different than our previously
-            // observed value return it. Otherwise fall back to waking later.
-            ChangeVal::Value(value) => {
-                let value = value.unbusy();
-                let value = Some(Ok(value));
-                if cmp != &value {
-                    return value;
-                }
-            }
-            // If the eventual is finalized from the writer end make sure that the final value
-            // (if any) is returned once as though it were a normal value. Then (possibly on
-            // a subsequent poll) return the Err.
-            ChangeVal::Finalized(value) => {
-                if let Some(value) = value.unbusy() {
-                    let value = Some(Ok(value));
-                    if cmp != &value {
-                        *lock = ChangeVal::Finalized(Busy::new(None));
-                        return value;
-                    }
-                }
-                return Some(Err(Closed));
-            }
-            // There is no update. The waker may need to be re-scheduled.
-            ChangeVal::None(_) | ChangeVal::Waker(_) => {}
-        }
-        *lock = ChangeVal::Waker(cx.waker().clone());
-        None
+    /// Like `poll`, but returns every write, even if it is redundant with
+    /// `prev` according to the dedup strategy.
+    pub fn poll_write(
+        &self,
+        prev: &mut Option<Result<Versioned<T>, Closed>>,
+        cx: &mut Context,
+    ) -> Poll<Result<Versioned<T>, Closed>> {
+        self.poll_with(prev, cx, None, true)
     }
 
-    pub fn set_value(&self, value: &Mutex<ChangeValNoWake<T>>) {
-        let prev = {
-            // To avoid race conditions BOTH locks MUST be held. This insures
-            // that if new values are pushed while subscribers are being
-            // notified there cannot be a time that a subscriber is notified
-            // with the old value. Instead, it might be notified with the new
-            // value twice. Notice that the former is apocalyptic (missed
-            // updates) and the later just drains some performance for an extra
-            // equality check on the receiving end.
-            let value = value.lock().unwrap();
-            let mut inner = self.inner.lock().unwrap();
+    fn poll_with(
+        &self,
+        prev: &mut Option<Result<Versioned<T>, Closed>>,
+        cx: &mut Context,
+        dedup: Option<&dyn Dedup<T>>,
+        may_defer: bool,
+    ) -> Poll<Result<Versioned<T>, Closed>> {
+        if let Some(Err(Closed)) = prev {
+            return Poll::Ready(Err(Closed));
+        }
+        loop {
+            let prev_version = match prev {
+                Some(Ok(prev)) => Some(prev.version),
+                _ => None,
+            };
 
-            // Move out of inner early for borrow checker.
-            let prev = mem::replace(inner.deref_mut(), ChangeVal::None(Busy::new(())));
+            let (next, finalized) = {
+                let last_write = self.unsubscribe_from.last_write.lock().unwrap();
 
-            match value.deref() {
-                ChangeValNoWake::None => {
-                    // Prev must be None. The only time set_value is called when
-                    // value is None is when the value has never before been set
-                    // (therefore prev is not ChangeValue::Finalized or
-                    // ChangeVal::Value) and the ChangeVal has no waker because
-                    // we are now adding it to the subscriber list.
-                    // If this assert fails, we would want `*inner = prev;`.
-                    debug_assert!(matches!(prev, ChangeVal::None(_)));
+                // To avoid race conditions the waker MUST be registered
+                // while holding the last_write lock if there is no update.
+                // Writers update last_write before waking subscribers, so
+                // either the update is visible here or the waker will be
+                // visible to the writer. Missing an update would be
+                // apocalyptic, whereas an extra wakeup only costs a poll.
+                match &*last_write {
+                    ChangeValNoWake::None => {
+                        self.change.set_waker(cx.waker());
+                        return Poll::Pending;
+                    }
+                    ChangeValNoWake::Value(next) if Some(next.version) == prev_version => {
+                        self.change.set_waker(cx.waker());
+                        return Poll::Pending;
+                    }
+                    ChangeValNoWake::Value(next) => (Some(next.clone()), false),
+                    ChangeValNoWake::Finalized(next) => (next.clone(), true),
+                }
+            };
+            // The lock is released before comparing values, which may be
+            // expensive.
+            self.change.set_busy();
 
-                    // Since we know this is None, there is no need to check
-                    // for the waker (below)
-                    return;
+            let next = match next {
+                Some(next) => next,
+                None => {
+                    *prev = Some(Err(Closed));
+                    return Poll::Ready(Err(Closed));
+                }
+            };
+            let is_same = match (prev.as_ref(), dedup) {
+                (Some(Ok(prev)), Some(dedup)) => prev.is_same(&next, dedup),
+                (Some(Ok(prev)), None) => prev.version == next.version,
+                _ => false,
+            };
+            #[cfg(feature = "tracing")]
+            match prev.as_ref() {
+                Some(Ok(_)) if is_same => {
+                    tracing::trace!(target: "eventuals", version = next.version, "skipped redundant value");
                 }
-                // There is an update.
-                ChangeValNoWake::Value(value) => {
-                    // The previous value must not have been finalized.
-                    // It is not possible to move from a finalized state to
-                    // then have updates.
-                    debug_assert!(!matches!(prev, ChangeVal::Finalized(_)));
-                    // Set the value.
-                    *inner = ChangeVal::Value(Busy::new(value.clone()));
+                Some(Ok(prev)) if next.version > prev.version + 1 => {
+                    let coalesced = next.version - prev.version - 1;
+                    tracing::trace!(target: "eventuals", coalesced, "coalesced values");
                 }
-                // If closing, this is more tricky because we want to preserve
-                // the last update (if any) so that the final value propagates
-                // all the way through.
-                ChangeValNoWake::Finalized(finalized) => {
-                    // Verify that it's not copying the final value over again
-                    // because in racey situations it may have been copied once
-                    // then had the value consumed. It wouldn't be the end of the
-                    // world to reset the finalized state, but would result in
-                    // some unnecessary work.
-                    if !matches!(prev, ChangeVal::Finalized(_)) {
-                        *inner = ChangeVal::Finalized(Busy::new(finalized.clone()));
+                _ => {}
+            }
+            if !is_same {
+                // Writes made by the task while processing this value carry
+                // its Acks downstream.
+                if !self.change.acks.is_empty() {
+                    let carried = ack::carry(&self.change.acks);
+                    if may_defer && self.change.defer(carried) {
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
                     }
                 }
-            };
-
-            prev
-
-            // Drop locks before calling wake()
-        };
-
-        // Race conditions here are OK. The worst that can happen
-        // is that Tasks are woken up unnecessarily. They would
-        // just return Pending the second time.
-        if let ChangeVal::Waker(waker) = prev {
-            waker.wake();
-        }
-    }
-}
-
-impl<T> Clone for Change<T> {
-    fn clone(&self) -> Self {
-        Self {
-            inner: self.inner.clone(),
+                *prev = Some(Ok(next.clone()));
+                return Poll::Ready(Ok(next));
+            }
+            if finalized {
+                *prev = Some(Err(Closed));
+                return Poll::Ready(Err(Closed));
+            }
+            // This was a redundant write. Remember the new version so that the
+            // values need not be compared again, and check for a newer write
+            // which may have happened while the lock was released. The value
+            // is kept, because later writes must be compared against the one
+            // the reader received. Otherwise a dedup strategy which is not
+            // transitive would drift.
+            if let Some(Ok(prev)) = prev {
+                prev.version = next.version;
+            }
         }
     }
 }
-
-impl<T> Eq for Change<T> {}
-impl<T> PartialEq for Change<T> {
-    fn eq(&self, other: &Self) -> bool {
-        self.inner.eq(&other.inner)
-    }
-}
-
-impl<T> Hash for Change<T> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.inner.hash(state)
-    }
-}
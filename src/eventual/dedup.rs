@@ -0,0 +1,90 @@
+//! Strategies for deciding whether a snapshot is redundant with the one that
+//! a reader previously observed. Redundant snapshots are skipped. Whatever the
+//! strategy, a reader never observes the same write twice.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+/// Decides whether observing `next` after `prev` would be redundant.
+/// See also `Eventual::with_dedup`.
+pub trait Dedup<T>: 'static + Send + Sync {
+    fn is_duplicate(&self, prev: &T, next: &T) -> bool;
+}
+
+/// Allows using a closure as a Dedup strategy.
+impl<T, F> Dedup<T> for F
+where
+    F: 'static + Send + Sync + Fn(&T, &T) -> bool,
+{
+    #[inline]
+    fn is_duplicate(&self, prev: &T, next: &T) -> bool {
+        self(prev, next)
+    }
+}
+
+/// Skip values which are equal to the previous one. This is the default.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ByEq;
+
+impl<T> Dedup<T> for ByEq
+where
+    T: PartialEq,
+{
+    #[inline]
+    fn is_duplicate(&self, prev: &T, next: &T) -> bool {
+        prev == next
+    }
+}
+
+/// Skip values which point to the same allocation as the previous one,
+/// without comparing the pointed to values. This is useful for large values
+/// which are expensive to compare.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ByPtr;
+
+impl<T> Dedup<Arc<T>> for ByPtr
+where
+    T: ?Sized,
+{
+    #[inline]
+    fn is_duplicate(&self, prev: &Arc<T>, next: &Arc<T>) -> bool {
+        Arc::ptr_eq(prev, next)
+    }
+}
+
+/// Skip values which hash the same as the previous one. Note that a hash
+/// collision would cause a distinct value to be skipped.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ByHash;
+
+impl ByHash {
+    fn hash<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl<T> Dedup<T> for ByHash
+where
+    T: Hash,
+{
+    #[inline]
+    fn is_duplicate(&self, prev: &T, next: &T) -> bool {
+        Self::hash(prev) == Self::hash(next)
+    }
+}
+
+/// Observe every write, even if the value is equal to the previous one.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoDedup;
+
+impl<T> Dedup<T> for NoDedup {
+    #[inline]
+    fn is_duplicate(&self, _prev: &T, _next: &T) -> bool {
+        false
+    }
+}
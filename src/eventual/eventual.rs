@@ -1,11 +1,16 @@
-use std::ops::DerefMut;
-
-use super::change::{ChangeReader, ChangeValNoWake};
+use super::blocking::block_on;
+use super::change::ChangeReader;
+use super::dedup::{ByEq, Dedup, NoDedup};
 use super::shared_state::SharedState;
+use super::writer::TaskGuard;
 use super::*;
-use crate::IntoReader;
+use crate::{
+    error::{catch_panic, catch_panic_with},
+    runtime, ClosedReason, IntoReader, PanicPolicy, Spawner,
+};
 use futures::channel::oneshot;
 use futures::never::Never;
+use std::{sync::Weak, time::Instant};
 use tokio::select;
 
 /// The entry point for getting the latest snapshots of values
@@ -19,35 +24,191 @@ impl<T> Eventual<T>
 where
     T: Value,
 {
-    /// Create a reader/writer pair.
-    pub fn new() -> (EventualWriter<T>, Self) {
+    /// Create a reader/writer pair. Readers skip values which are equal to
+    /// the one they previously observed. See also `with_dedup` for values
+    /// which are not PartialEq.
+    pub fn new() -> (EventualWriter<T>, Self)
+    where
+        T: PartialEq,
+    {
+        Self::with_dedup(ByEq)
+    }
+
+    /// Create a reader/writer pair which uses the provided strategy to decide
+    /// which values are redundant for readers. For example, `NoDedup` for
+    /// values which are expensive to compare, or `ByPtr` to compare `Arc`s
+    /// by address.
+    pub fn with_dedup<D>(dedup: D) -> (EventualWriter<T>, Self)
+    where
+        D: Dedup<T>,
+    {
+        Self::with_shared_dedup(Arc::new(dedup))
+    }
+
+    /// Like `with_dedup`, but shares the strategy of another Eventual. Used by
+    /// combinators which pass values through from a source.
+    pub(crate) fn with_shared_dedup(dedup: Arc<dyn Dedup<T>>) -> (EventualWriter<T>, Self) {
         let (sender, receiver) = oneshot::channel();
-        let state = Arc::new(SharedState::new(sender));
+        let state = Arc::new(SharedState::new(sender, dedup));
         (EventualWriter::new(&state, receiver), Eventual { state })
     }
 
     /// Create an eventual having a final value. This is useful for creating
     /// "mock" eventuals to pass into consumers.
     pub fn from_value(value: T) -> Self {
-        let (mut writer, eventual) = Eventual::new();
+        // There is only ever one write, so there is nothing to dedup.
+        let (mut writer, eventual) = Eventual::with_dedup(NoDedup);
         writer.write(value);
         eventual
     }
 
     /// A helper for spawning a task which writes to an eventual.
     /// These are used extensively within the library for eventuals
-    /// which update continuously over time.
+    /// which update continuously over time. The task is spawned with the
+    /// default Spawner. See also `spawn_on`.
     pub fn spawn<F, Fut>(f: F) -> Self
     where
+        T: PartialEq,
         F: 'static + Send + FnOnce(EventualWriter<T>) -> Fut,
         Fut: Future<Output = Result<Never, Closed>> + Send,
     {
-        let (writer, eventual) = Eventual::new();
-        tokio::spawn(async move {
+        Self::spawn_on(&runtime::DefaultSpawner, f)
+    }
+
+    /// Like `spawn`, but with a specific dedup strategy. See also
+    /// `with_dedup`.
+    pub fn spawn_with_dedup<D, F, Fut>(dedup: D, f: F) -> Self
+    where
+        D: Dedup<T>,
+        F: 'static + Send + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        Self::spawn_stage_with_dedup("eventuals::spawn", Arc::new(dedup), f)
+    }
+
+    /// Like `spawn`, but runs the task with a specific Spawner. This can be
+    /// used to run a stage of a pipeline on a particular runtime or thread
+    /// pool.
+    pub fn spawn_on<S, F, Fut>(spawner: &S, f: F) -> Self
+    where
+        T: PartialEq,
+        S: Spawner + ?Sized,
+        F: 'static + Send + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        Self::spawn_stage_on(spawner, "eventuals::spawn", Arc::new(ByEq), f)
+    }
+
+    /// Like `spawn`, but names the task and its span after the kind of stage,
+    /// such as "eventuals::map". Used by combinators.
+    pub(crate) fn spawn_stage<F, Fut>(kind: &'static str, f: F) -> Self
+    where
+        T: PartialEq,
+        F: 'static + Send + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        Self::spawn_stage_with_dedup(kind, Arc::new(ByEq), f)
+    }
+
+    /// Like `spawn_stage`, but with a specific dedup strategy, such as the
+    /// one of the source for combinators which pass values through.
+    pub(crate) fn spawn_stage_with_dedup<F, Fut>(
+        kind: &'static str,
+        dedup: Arc<dyn Dedup<T>>,
+        f: F,
+    ) -> Self
+    where
+        F: 'static + Send + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        Self::spawn_stage_on(&runtime::DefaultSpawner, kind, dedup, f)
+    }
+
+    fn spawn_stage_on<S, F, Fut>(
+        spawner: &S,
+        kind: &'static str,
+        dedup: Arc<dyn Dedup<T>>,
+        f: F,
+    ) -> Self
+    where
+        S: Spawner + ?Sized,
+        F: 'static + Send + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        let (writer, eventual) = Eventual::with_shared_dedup(dedup);
+        let guard = TaskGuard::new(&writer);
+        let task = async move {
+            // The panic is reported before the future, and the writer it
+            // owns, are dropped.
             select!(
-                _ = writer.closed() => {}
-                _ = async { f(writer).await }  => {}
+                _ = guard.closed() => {}
+                _ = catch_panic_with(|| f(writer), |panic| {
+                    let _ = guard.handle_panic(panic, false);
+                }) => {}
             );
+            guard.complete();
+        };
+        #[cfg(feature = "tracing")]
+        let task = tracing::Instrument::instrument(task, eventual.span(kind));
+        spawner.spawn_named(kind, Box::pin(idle::scoped(Box::pin(task))));
+        eventual
+    }
+
+    /// Like `spawn`, but the task only runs while the Eventual has readers.
+    /// The task is started when the first reader subscribes, and is dropped
+    /// when the last reader is dropped. If readers subscribe again later the
+    /// task is restarted by calling `f` again. The last written value remains
+    /// visible while the task is stopped. If the task completes, the Eventual
+    /// is closed.
+    pub fn lazy<F, Fut>(f: F) -> Self
+    where
+        T: PartialEq,
+        F: 'static + Send + FnMut(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        Self::lazy_on(&runtime::DefaultSpawner, f)
+    }
+
+    /// Like `lazy`, but runs the task with a specific Spawner. See also
+    /// `spawn_on`.
+    pub fn lazy_on<S, F, Fut>(spawner: &S, mut f: F) -> Self
+    where
+        T: PartialEq,
+        S: Spawner + ?Sized,
+        F: 'static + Send + FnMut(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        let (writer, eventual) = Eventual::new();
+        let mut subscribers = writer.subscribers().subscribe();
+        let guard = TaskGuard::new(&writer);
+        runtime::spawn_on(spawner, async move {
+            // The task holds the writer for as long as it runs, so that the
+            // Eventual stays open while the producer is stopped.
+            let writer = &writer;
+            // The subscriber count closes once the Eventual is dropped.
+            while let Ok(count) = subscribers.next().await {
+                if count == 0 {
+                    continue;
+                }
+                let idle = async {
+                    loop {
+                        match subscribers.next().await {
+                            Ok(0) | Err(Closed) => return,
+                            Ok(_) => {}
+                        }
+                    }
+                };
+                select!(
+                    result = catch_panic(|| f(writer.clone())) => {
+                        if let Err(panic) = result {
+                            let _ = writer.handle_panic(panic, false);
+                        }
+                        break;
+                    }
+                    _ = idle => {}
+                );
+            }
+            guard.complete();
         });
         eventual
     }
@@ -69,44 +230,159 @@ where
     /// snapshot is returned depends on when the Future is polled (as opposed
     /// to when the Future is created)
     pub fn value(&self) -> ValueFuture<T> {
-        let change = self.state.clone().subscribe();
-        ValueFuture {
-            change: Some(change),
-        }
+        ValueFuture::new(&self.state)
+    }
+
+    /// Block the current thread until a snapshot is available. This is the
+    /// equivalent of `value` for code running outside of any async runtime.
+    /// Panics if called from within an async context.
+    #[track_caller]
+    pub fn blocking_value(&self) -> Result<T, Closed> {
+        block_on(self.value(), None).unwrap()
     }
 
     /// Get a snapshot of the current value of this Eventual, if any,
     /// without waiting.
     pub fn value_immediate(&self) -> Option<T> {
-        match self.state.last_write.lock().unwrap().deref_mut() {
-            ChangeValNoWake::None => None,
-            ChangeValNoWake::Value(t) => Some(t.clone()),
-            ChangeValNoWake::Finalized(t) => t.clone(),
+        self.value_immediate_versioned().map(|(value, _)| value)
+    }
+
+    /// Like `value_immediate`, but also returns the version of the write
+    /// which produced the snapshot.
+    pub fn value_immediate_versioned(&self) -> Option<(T, u64)> {
+        self.state.snapshot().map(|v| {
+            let version = v.version;
+            (v.into_value(), version)
+        })
+    }
+
+    /// When the current value was written, if there is one. For the output of
+    /// `map` and `join` this is when the source was written.
+    pub fn last_write_at(&self) -> Option<Instant> {
+        self.state.snapshot().map(|v| v.written_at)
+    }
+
+    /// Call `f` with a reference to the current value of this Eventual, if
+    /// any, without cloning it. The value is a shared snapshot, so `f` does
+    /// not block writers and later writes do not affect it.
+    pub fn with_value<F, R>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.state.snapshot().map(|v| f(&v.value))
+    }
+
+    /// Why the Eventual was closed, or None if it is not closed.
+    pub fn closed_reason(&self) -> Option<ClosedReason> {
+        self.state.closed_reason.get().cloned()
+    }
+
+    /// Set what the stage which writes to this Eventual, such as the task of a
+    /// `map`, does when its closure panics. For example
+    /// `source.map(f).with_panic_policy(PanicPolicy::Skip)`. The default is
+    /// `PanicPolicy::Close`. This applies to panics from then on, so set it
+    /// when creating the stage.
+    pub fn with_panic_policy(self, policy: PanicPolicy) -> Self {
+        self.state.set_panic_policy(policy);
+        self
+    }
+
+    /// Name the stage which writes to this Eventual, such as the task of a
+    /// `map`, for its tracing spans and events. For example
+    /// `source.map(f).named("routing_table")`. This has no effect unless the
+    /// `tracing` feature is enabled.
+    pub fn named(self, name: &str) -> Self {
+        #[cfg(feature = "tracing")]
+        self.state.span("eventuals::eventual").record("name", name);
+        #[cfg(not(feature = "tracing"))]
+        let _ = name;
+        self
+    }
+
+    /// The span of the stage which writes to this Eventual, created with
+    /// `kind` if there is none yet.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self, kind: &'static str) -> tracing::Span {
+        self.state.span(kind).clone()
+    }
+
+    /// Create a handle which does not keep the Eventual alive. Once every
+    /// Eventual and reader has been dropped the writer observes `closed`,
+    /// even if weak handles remain.
+    pub fn downgrade(&self) -> WeakEventual<T> {
+        WeakEventual {
+            state: Arc::downgrade(&self.state),
         }
     }
 
-    #[cfg(feature = "trace")]
+    /// The number of readers currently subscribed to this Eventual. See also
+    /// `EventualWriter::subscribers`.
     pub fn subscriber_count(&self) -> usize {
         self.state.subscribers.lock().unwrap().len()
     }
 }
 
+/// A handle to an Eventual which does not keep it alive. See also
+/// `Eventual::downgrade`.
+pub struct WeakEventual<T> {
+    state: Weak<SharedState<T>>,
+}
+
+impl<T> WeakEventual<T>
+where
+    T: Value,
+{
+    /// Get the Eventual back, if it has not been dropped.
+    pub fn upgrade(&self) -> Option<Eventual<T>> {
+        self.state.upgrade().map(|state| Eventual { state })
+    }
+
+    /// Get a snapshot of the current value of the Eventual, if it has not
+    /// been dropped and has a value, without waiting.
+    pub fn value_immediate(&self) -> Option<T> {
+        self.state
+            .upgrade()
+            .and_then(|state| state.snapshot())
+            .map(|v| v.into_value())
+    }
+}
+
+impl<T> Clone for WeakEventual<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
 pub struct ValueFuture<T> {
     change: Option<ChangeReader<T>>,
 }
 
+impl<T> ValueFuture<T>
+where
+    T: LocalValue,
+{
+    pub(crate) fn new(state: &Arc<SharedState<T>>) -> Self {
+        Self {
+            change: Some(state.clone().subscribe()),
+        }
+    }
+}
+
 impl<T> Future for ValueFuture<T>
 where
-    T: Value,
+    T: LocalValue,
 {
     type Output = Result<T, Closed>;
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let update = self.change.as_mut().unwrap().change.poll(&None, cx);
+        let update = self.change.as_mut().unwrap().poll(&mut None, cx);
         match update {
-            None => Poll::Pending,
-            Some(value) => {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(value) => {
                 self.change = None;
-                Poll::Ready(value)
+                Poll::Ready(value.map(|v| v.into_value()))
             }
         }
     }
@@ -1,4 +1,4 @@
-use crate::*;
+use crate::{dedup::Dedup, *};
 use futures::Future;
 use std::time::Duration;
 
@@ -8,17 +8,38 @@ pub trait EventualExt: Sized + IntoReader {
     fn map<F, O, Fut>(self, f: F) -> Eventual<O>
     where
         F: 'static + Send + FnMut(Self::Output) -> Fut,
-        O: Value,
+        O: Value + PartialEq,
         Fut: Send + Future<Output = O>,
     {
         map(self, f)
     }
 
+    #[inline]
+    fn map_with_dedup<D, F, O, Fut>(self, dedup: D, f: F) -> Eventual<O>
+    where
+        D: Dedup<O>,
+        F: 'static + Send + FnMut(Self::Output) -> Fut,
+        O: Value,
+        Fut: Send + Future<Output = O>,
+    {
+        map_with_dedup(self, dedup, f)
+    }
+
     #[inline]
     fn throttle(self, duration: Duration) -> Eventual<Self::Output> {
         throttle(self, duration)
     }
 
+    #[inline]
+    fn expire_after(self, ttl: Duration, on_expire: OnExpire) -> Eventual<Self::Output> {
+        expire_after(self, ttl, on_expire)
+    }
+
+    #[inline]
+    fn liveness(self, ttl: Duration) -> Eventual<bool> {
+        liveness(self, ttl)
+    }
+
     #[inline]
     fn pipe<F>(self, f: F) -> PipeHandle
     where
@@ -41,7 +62,7 @@ pub trait EventualExt: Sized + IntoReader {
     where
         F: 'static + Send + FnMut(Self::Output) -> Fut,
         E: 'static + Send + Sync + FnMut(Err) -> FutE,
-        Ok: Value,
+        Ok: Value + PartialEq,
         Err: Value,
         Fut: Send + Future<Output = Result<Ok, Err>>,
         FutE: Send + Future<Output = ()>,
@@ -67,6 +88,7 @@ where
     fn handle_errors<F>(self, f: F) -> Eventual<Ok>
     where
         F: 'static + Send + FnMut(Err),
+        Ok: PartialEq,
     {
         #[allow(deprecated)]
         handle_errors(self, f)
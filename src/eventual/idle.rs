@@ -0,0 +1,223 @@
+use std::{
+    cell::RefCell,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+use tokio::sync::Notify;
+
+use super::ack::{self, TaskAcks};
+
+// Counts the readers which are not waiting on a new value.
+struct BusyCount {
+    count: AtomicUsize,
+    waker: Notify,
+}
+
+impl BusyCount {
+    const fn new() -> Self {
+        Self {
+            count: AtomicUsize::new(0),
+            waker: Notify::const_new(),
+        }
+    }
+
+    fn set_busy(&self) {
+        self.count.fetch_add(1, SeqCst);
+    }
+
+    fn clear_busy(&self) {
+        let prev = self.count.fetch_sub(1, SeqCst);
+        debug_assert!(prev != 0);
+        if prev == 1 {
+            self.waker.notify_waiters();
+        }
+    }
+
+    async fn idle(&self) {
+        loop {
+            let notified = self.waker.notified();
+            if self.count.load(SeqCst) == 0 {
+                return;
+            }
+            notified.await
+        }
+    }
+}
+
+impl Default for BusyCount {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "trace")]
+static GLOBAL: BusyCount = BusyCount::new();
+
+/// Ready once all eventuals readers are waiting on a new value. _Generally_
+/// speaking, it is possible to ensure that a change has propagated through
+/// an eventuals pipeline using this method. However, there is no guarantee
+/// that this will complete in a timely fashion if ever. A sufficiently
+/// layered pipeline that is always moving values through may never be idle.
+/// So, this is only useful in isolated tests. See also `IdleScope`, which
+/// only waits on a single pipeline.
+#[cfg(feature = "trace")]
+pub async fn idle() {
+    GLOBAL.idle().await
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<IdleScope>> = const { RefCell::new(None) };
+}
+
+/// Tracks whether the readers of a group of eventuals are all waiting on a
+/// new value, like `idle` but without being affected by unrelated pipelines.
+/// Eventuals, combinators and readers created while the scope is entered
+/// belong to it, as do those created later by the tasks of its combinators.
+///
+/// The scope is tracked per thread while polling, so tasks spawned directly
+/// with the runtime (such as with `tokio::spawn`) do not inherit it, and
+/// readers subscribed by them are invisible to `idle`. Spawn such tasks with
+/// `IdleScope::wrap` to include them.
+///
+/// ```
+/// # use eventuals::*;
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let scope = IdleScope::new();
+/// let (mut writer, eventual) = Eventual::new();
+/// let doubled = {
+///     let _enter = scope.enter();
+///     eventual.map(|v: u32| async move { v * 2 })
+/// };
+/// writer.write(1);
+/// scope.idle().await;
+/// assert_eq!(doubled.value_immediate(), Some(2));
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct IdleScope {
+    busy: Arc<BusyCount>,
+}
+
+impl IdleScope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Make this the current scope of the calling thread until the returned
+    /// guard is dropped.
+    pub fn enter(&self) -> IdleScopeGuard {
+        let prev = CURRENT.with(|current| current.replace(Some(self.clone())));
+        IdleScopeGuard {
+            prev,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Run `future` in this scope, so that readers and eventuals it creates
+    /// belong to the scope. For example
+    /// `tokio::spawn(scope.wrap(async move { ... }))`.
+    pub fn wrap<F>(&self, future: F) -> impl Future<Output = F::Output>
+    where
+        F: Future,
+    {
+        Scoped {
+            scope: Some(self.clone()),
+            acks: TaskAcks::default(),
+            future: Box::pin(future),
+        }
+    }
+
+    /// Ready once every reader in this scope is waiting on a new value. The
+    /// same caveats apply as for `idle`, except that only the pipelines in
+    /// this scope need to settle.
+    pub async fn idle(&self) {
+        self.busy.idle().await
+    }
+}
+
+/// Restores the previous scope when dropped. See `IdleScope::enter`.
+pub struct IdleScopeGuard {
+    prev: Option<IdleScope>,
+    // The guard restores the scope of the thread which created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for IdleScopeGuard {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        CURRENT.with(|current| *current.borrow_mut() = prev);
+    }
+}
+
+/// The scope of the calling thread, if any.
+pub(crate) fn current() -> Option<IdleScope> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Runs `future` in the scope of the calling thread, so that anything it
+/// creates while polled belongs to the same scope. The future also gets its
+/// own TaskAcks, so that its writes carry the Acks of the values it observed.
+pub(crate) fn scoped<F>(future: F) -> Scoped<F>
+where
+    F: Future + Unpin,
+{
+    Scoped {
+        scope: current(),
+        acks: TaskAcks::default(),
+        future,
+    }
+}
+
+pub(crate) struct Scoped<F> {
+    scope: Option<IdleScope>,
+    acks: TaskAcks,
+    future: F,
+}
+
+impl<F> Future for Scoped<F>
+where
+    F: Future + Unpin,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let _enter = this.scope.as_ref().map(IdleScope::enter);
+        // Tracking which subscribers the task observes is only needed while
+        // an acknowledged write is in flight. See also `Change::defer`.
+        let _acks = ack::pending().then(|| this.acks.enter());
+        Pin::new(&mut this.future).poll(cx)
+    }
+}
+
+/// A reader is busy for as long as it is not waiting on a new value.
+pub(crate) struct Busy(Option<Arc<BusyCount>>);
+
+impl Busy {
+    pub fn new(scope: Option<&IdleScope>) -> Self {
+        #[cfg(feature = "trace")]
+        GLOBAL.set_busy();
+        let busy = scope.map(|scope| scope.busy.clone());
+        if let Some(busy) = &busy {
+            busy.set_busy();
+        }
+        Self(busy)
+    }
+}
+
+impl Drop for Busy {
+    fn drop(&mut self) {
+        #[cfg(feature = "trace")]
+        GLOBAL.clear_busy();
+        if let Some(busy) = &self.0 {
+            busy.clear_busy();
+        }
+    }
+}
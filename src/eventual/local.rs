@@ -0,0 +1,312 @@
+use super::{
+    change::Versioned,
+    dedup::{ByEq, Dedup, NoDedup},
+    eventual::ValueFuture,
+    reader::Next,
+    writer::TaskGuard,
+    *,
+};
+use crate::{
+    error::catch_panic_with, local, ClosedReason, LocalIntoReader, PanicPolicy, PipeHandle,
+};
+use futures::{channel::oneshot, never::Never};
+use std::time::Duration;
+use tokio::select;
+
+/// Like Eventual, but for values which are not Send. Tasks which write to a
+/// LocalEventual are spawned with `tokio::task::spawn_local`, so they must be
+/// created from within a `tokio::task::LocalSet`. The semantics of observing
+/// snapshots are the same as for Eventual.
+pub struct LocalEventual<T> {
+    state: Arc<SharedState<T>>,
+}
+
+impl<T> LocalEventual<T>
+where
+    T: LocalValue,
+{
+    /// Create a reader/writer pair. See also `with_dedup` for values which
+    /// are not PartialEq.
+    pub fn new() -> (EventualWriter<T>, Self)
+    where
+        T: PartialEq,
+    {
+        Self::with_dedup(ByEq)
+    }
+
+    /// Create a reader/writer pair with a specific dedup strategy.
+    /// See also `Eventual::with_dedup`.
+    pub fn with_dedup<D>(dedup: D) -> (EventualWriter<T>, Self)
+    where
+        D: Dedup<T>,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let state = Arc::new(SharedState::new(sender, Arc::new(dedup)));
+        (
+            EventualWriter::new(&state, receiver),
+            LocalEventual { state },
+        )
+    }
+
+    /// Create an eventual having a final value.
+    pub fn from_value(value: T) -> Self {
+        let (mut writer, eventual) = LocalEventual::with_dedup(NoDedup);
+        writer.write(value);
+        eventual
+    }
+
+    /// A helper for spawning a local task which writes to an eventual.
+    /// See also `Eventual::spawn`.
+    pub fn spawn<F, Fut>(f: F) -> Self
+    where
+        T: PartialEq,
+        F: 'static + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>>,
+    {
+        Self::spawn_stage("eventuals::spawn", f)
+    }
+
+    /// Like `spawn`, but with a specific dedup strategy.
+    /// See also `Eventual::spawn_with_dedup`.
+    pub fn spawn_with_dedup<D, F, Fut>(dedup: D, f: F) -> Self
+    where
+        D: Dedup<T>,
+        F: 'static + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>>,
+    {
+        Self::spawn_stage_with_dedup("eventuals::spawn", Arc::new(dedup), f)
+    }
+
+    /// Like `spawn`, but names the task and its span after the kind of stage.
+    /// Used by combinators.
+    pub(crate) fn spawn_stage<F, Fut>(kind: &'static str, f: F) -> Self
+    where
+        T: PartialEq,
+        F: 'static + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>>,
+    {
+        Self::spawn_stage_with_dedup(kind, Arc::new(ByEq), f)
+    }
+
+    /// Like `spawn_stage`, but with a specific dedup strategy.
+    pub(crate) fn spawn_stage_with_dedup<F, Fut>(
+        kind: &'static str,
+        dedup: Arc<dyn Dedup<T>>,
+        f: F,
+    ) -> Self
+    where
+        F: 'static + FnOnce(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>>,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let state = Arc::new(SharedState::new(sender, dedup));
+        let writer = EventualWriter::new(&state, receiver);
+        spawn_local(kind, writer, f);
+        LocalEventual { state }
+    }
+
+    /// Set what the stage which writes to this LocalEventual does when its
+    /// closure panics. See also `Eventual::with_panic_policy`.
+    pub fn with_panic_policy(self, policy: PanicPolicy) -> Self {
+        self.state.set_panic_policy(policy);
+        self
+    }
+
+    /// Name the stage which writes to this LocalEventual for its tracing spans
+    /// and events. See also `Eventual::named`.
+    pub fn named(self, name: &str) -> Self {
+        #[cfg(feature = "tracing")]
+        self.state.span("eventuals::eventual").record("name", name);
+        #[cfg(not(feature = "tracing"))]
+        let _ = name;
+        self
+    }
+
+    /// Subscribe to present and future snapshots of the value in this
+    /// LocalEventual. See also `Eventual::subscribe`.
+    pub fn subscribe(&self) -> LocalEventualReader<T> {
+        LocalEventualReader {
+            inner: EventualReader::new(self.state.clone()),
+        }
+    }
+
+    /// Get a future that resolves with a snapshot of the present value of the
+    /// LocalEventual, if any, or a future snapshot if none is available.
+    pub fn value(&self) -> ValueFuture<T> {
+        ValueFuture::new(&self.state)
+    }
+
+    /// Get a snapshot of the current value of this LocalEventual, if any,
+    /// without waiting.
+    pub fn value_immediate(&self) -> Option<T> {
+        self.state.snapshot().map(|v| v.into_value())
+    }
+}
+
+/// Spawns a local task which writes to `writer`, naming the task and its span
+/// after the kind of stage, such as "eventuals::map".
+pub(crate) fn spawn_local<T, F, Fut>(kind: &'static str, writer: EventualWriter<T>, f: F)
+where
+    T: LocalValue,
+    F: 'static + FnOnce(EventualWriter<T>) -> Fut,
+    Fut: Future<Output = Result<Never, Closed>>,
+{
+    #[cfg(feature = "tracing")]
+    let span = writer.span(kind);
+    let guard = TaskGuard::new(&writer);
+    let task = async move {
+        // The panic is reported before the future, and the writer it
+        // owns, are dropped.
+        select!(
+            _ = guard.closed() => {}
+            _ = catch_panic_with(|| f(writer), |panic| {
+                let _ = guard.handle_panic(panic, false);
+            }) => {}
+        );
+        guard.complete();
+    };
+    #[cfg(feature = "tracing")]
+    let task = tracing::Instrument::instrument(task, span);
+    let task = idle::scoped(Box::pin(task));
+    #[cfg(all(tokio_unstable, feature = "tracing"))]
+    let _ = tokio::task::Builder::new().name(kind).spawn_local(task);
+    #[cfg(not(all(tokio_unstable, feature = "tracing")))]
+    {
+        let _ = kind;
+        tokio::task::spawn_local(task);
+    }
+}
+
+impl<T> Clone for LocalEventual<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Observes snapshots of a LocalEventual. See also `EventualReader`.
+pub struct LocalEventualReader<T> {
+    inner: EventualReader<T>,
+}
+
+impl<T> LocalEventualReader<T>
+where
+    T: LocalValue,
+{
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Next<'_, T> {
+        self.inner.next()
+    }
+
+    /// Why the LocalEventual was closed, or None if it is not closed.
+    pub fn closed_reason(&self) -> Option<ClosedReason> {
+        self.inner.closed_reason()
+    }
+
+    pub(crate) async fn next_or_close<U>(&mut self, writer: &EventualWriter<U>) -> Result<T, Closed>
+    where
+        U: LocalValue,
+    {
+        self.inner.next_or_close(writer).await
+    }
+
+    /// The dedup strategy of the LocalEventual being read.
+    pub(crate) fn dedup(&self) -> Arc<dyn Dedup<T>> {
+        self.inner.dedup()
+    }
+
+    pub(crate) async fn next_snapshot_or_close<U>(
+        &mut self,
+        writer: &EventualWriter<U>,
+    ) -> Result<Versioned<T>, Closed>
+    where
+        U: LocalValue,
+    {
+        self.inner.next_snapshot_or_close(writer).await
+    }
+}
+
+impl<T> Clone for LocalEventualReader<T>
+where
+    T: LocalValue,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> LocalIntoReader for LocalEventualReader<T>
+where
+    T: LocalValue,
+{
+    type Output = T;
+    #[inline]
+    fn into_reader(self) -> LocalEventualReader<Self::Output> {
+        self
+    }
+}
+
+impl<T> LocalIntoReader for &'_ LocalEventual<T>
+where
+    T: LocalValue,
+{
+    type Output = T;
+    #[inline]
+    fn into_reader(self) -> LocalEventualReader<Self::Output> {
+        self.subscribe()
+    }
+}
+
+impl<T> LocalIntoReader for LocalEventual<T>
+where
+    T: LocalValue,
+{
+    type Output = T;
+    #[inline]
+    fn into_reader(self) -> LocalEventualReader<Self::Output> {
+        self.subscribe()
+    }
+}
+
+/// Fluent style API extensions for any LocalEventual reader.
+pub trait LocalEventualExt: Sized + LocalIntoReader {
+    #[inline]
+    fn map<F, O, Fut>(self, f: F) -> LocalEventual<O>
+    where
+        F: 'static + FnMut(Self::Output) -> Fut,
+        O: LocalValue + PartialEq,
+        Fut: Future<Output = O>,
+    {
+        local::map(self, f)
+    }
+
+    #[inline]
+    fn map_with_dedup<D, F, O, Fut>(self, dedup: D, f: F) -> LocalEventual<O>
+    where
+        D: Dedup<O>,
+        F: 'static + FnMut(Self::Output) -> Fut,
+        O: LocalValue,
+        Fut: Future<Output = O>,
+    {
+        local::map_with_dedup(self, dedup, f)
+    }
+
+    #[inline]
+    fn throttle(self, duration: Duration) -> LocalEventual<Self::Output> {
+        local::throttle(self, duration)
+    }
+
+    #[inline]
+    fn pipe<F>(self, f: F) -> PipeHandle
+    where
+        F: 'static + FnMut(Self::Output),
+    {
+        local::pipe(self, f)
+    }
+}
+
+impl<E> LocalEventualExt for E where E: LocalIntoReader {}
@@ -1,29 +1,53 @@
 use super::Closed;
 use std::{
     future::Future,
-    ops::Deref,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
 };
 
+mod ack;
+mod blocking;
 mod change;
+pub mod dedup;
+#[allow(clippy::module_inception)]
 mod eventual;
 mod eventual_ext;
+mod idle;
+#[cfg(feature = "tokio-runtime")]
+mod local;
 mod ptr;
 mod reader;
 mod shared_state;
+#[cfg(feature = "stream")]
+mod stream;
+mod supervise;
+mod watch;
 mod writer;
 
-pub(self) use {crate::Value, shared_state::*};
+use {
+    crate::{LocalValue, Value},
+    shared_state::*,
+};
 
 pub use {
-    eventual::Eventual,
+    eventual::{Eventual, WeakEventual},
     eventual_ext::{EventualExt, TryEventualExt},
+    idle::{IdleScope, IdleScopeGuard},
     ptr::Ptr,
-    reader::EventualReader,
-    writer::EventualWriter,
+    reader::{EventualReader, Next, NextRef, NextTimestamped, NextVersioned},
+    supervise::RestartPolicy,
+    writer::{EventualWriter, WriteOutcome},
 };
 
+pub(crate) use idle::scoped;
+#[cfg(feature = "tokio-runtime")]
+pub(crate) use local::spawn_local;
+#[cfg(feature = "tokio-runtime")]
+pub use local::{LocalEventual, LocalEventualExt, LocalEventualReader};
+
+#[cfg(feature = "stream")]
+pub use stream::LatestStream;
+
 #[cfg(feature = "trace")]
-pub use change::idle;
+pub use idle::idle;
@@ -71,7 +71,7 @@ impl<T> Ord for Ptr<T> {
 impl<T> PartialOrd for Ptr<T> {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.inner.partial_cmp(&other.inner)
+        Some(self.cmp(other))
     }
 }
 
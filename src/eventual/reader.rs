@@ -1,5 +1,12 @@
-use super::{change::ChangeReader, *};
-use crate::{error::Closed, IntoReader};
+use super::{
+    blocking::block_on,
+    change::{ChangeReader, Versioned},
+    dedup::Dedup,
+    *,
+};
+use crate::{error::Closed, ClosedReason, IntoReader};
+use futures::{future::poll_fn, task::noop_waker_ref};
+use std::time::{Duration, Instant};
 
 // It's tempting here to provide some API that treats the Eventual like a
 // Stream. That would be bad though, because it would expose all the APIs that
@@ -11,7 +18,7 @@ use crate::{error::Closed, IntoReader};
 // value which infers no sequence and may drop intermediate values.
 pub struct EventualReader<T> {
     change: ChangeReader<T>,
-    prev: Option<Result<T, Closed>>,
+    prev: Option<Result<Versioned<T>, Closed>>,
 }
 
 impl<T> IntoReader for EventualReader<T>
@@ -30,58 +37,264 @@ pub struct Next<'a, T> {
 
 impl<'a, T> Future for Next<'a, T>
 where
-    T: Value,
+    T: LocalValue,
 {
     type Output = Result<T, Closed>;
-    // TODO: This is currently checking for a pushed value, but that will require
-    // eg: map() to run in separate tasks. It might be desirable to have this poll
-    // the future that would produce values. But... that may be very complex. A
-    // refactor may be necessary.
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let update = self.eventual.change.change.poll(&self.eventual.prev, cx);
-        match update {
-            None => Poll::Pending,
-            Some(value) => {
-                self.eventual.prev = Some(value.clone());
-                Poll::Ready(value)
-            }
-        }
+        self.eventual
+            .poll_versioned(cx)
+            .map(|update| update.map(|v| v.into_value()))
+    }
+}
+
+pub struct NextVersioned<'a, T> {
+    eventual: &'a mut EventualReader<T>,
+}
+
+impl<'a, T> Future for NextVersioned<'a, T>
+where
+    T: LocalValue,
+{
+    type Output = Result<(T, u64), Closed>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.eventual.poll_versioned(cx).map(|update| {
+            update.map(|v| {
+                let version = v.version;
+                (v.into_value(), version)
+            })
+        })
+    }
+}
+
+pub struct NextTimestamped<'a, T> {
+    eventual: &'a mut EventualReader<T>,
+}
+
+impl<'a, T> Future for NextTimestamped<'a, T>
+where
+    T: LocalValue,
+{
+    type Output = Result<(T, Instant), Closed>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.eventual.poll_versioned(cx).map(|update| {
+            update.map(|v| {
+                let written_at = v.written_at;
+                (v.into_value(), written_at)
+            })
+        })
+    }
+}
+
+pub struct NextRef<'a, T> {
+    eventual: &'a mut EventualReader<T>,
+}
+
+impl<'a, T> Future for NextRef<'a, T>
+where
+    T: LocalValue,
+{
+    type Output = Result<Arc<T>, Closed>;
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.eventual
+            .poll_versioned(cx)
+            .map(|update| update.map(|v| v.value))
     }
 }
 
 impl<T> EventualReader<T>
 where
-    T: Value,
+    T: LocalValue,
 {
-    pub fn next(&mut self) -> Next<T> {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Next<'_, T> {
         Next { eventual: self }
     }
 
+    /// Like `next`, but also resolves with the version of the write which
+    /// produced the snapshot. Versions increase with each write to the
+    /// Eventual, so two observations with the same version came from the same
+    /// write.
+    pub fn next_versioned(&mut self) -> NextVersioned<'_, T> {
+        NextVersioned { eventual: self }
+    }
+
+    /// Like `next`, but also resolves with the time at which the snapshot was
+    /// written. Combinators which pass values through or derive them from a
+    /// source (such as `map`, `join`, `throttle` and `retry`) carry the time
+    /// at which the source was written, so the age of data can be judged at
+    /// the end of a pipeline. Combinators which produce values of their own,
+    /// such as `timer`, `liveness` and the initial value of `init_with`, use
+    /// the time of their write.
+    pub fn next_timestamped(&mut self) -> NextTimestamped<'_, T> {
+        NextTimestamped { eventual: self }
+    }
+
+    /// Like `next`, but resolves with the shared snapshot instead of cloning
+    /// the value out of it. This is useful for large values which are
+    /// expensive to clone.
+    pub fn next_ref(&mut self) -> NextRef<'_, T> {
+        NextRef { eventual: self }
+    }
+
+    /// Block the current thread until the next snapshot is available. This is
+    /// for code running outside of any async runtime, such as FFI callbacks or
+    /// thread pool workers. Panics if called from within an async context.
+    /// See also `next`.
+    #[track_caller]
+    pub fn blocking_next(&mut self) -> Result<T, Closed> {
+        block_on(self.next(), None).unwrap()
+    }
+
+    /// Like `blocking_next`, but gives up and returns None once `timeout` has
+    /// elapsed without a new snapshot.
+    #[track_caller]
+    pub fn blocking_next_timeout(&mut self, timeout: Duration) -> Option<Result<T, Closed>> {
+        block_on(self.next(), Some(timeout))
+    }
+
+    /// Returns the next snapshot if one is available without waiting, marking
+    /// it observed as `next` would. Returns None if there is no unobserved
+    /// update.
+    pub fn try_next(&mut self) -> Option<Result<T, Closed>> {
+        Self::poll_now(&self.change, &mut self.prev).map(|update| update.map(|v| v.into_value()))
+    }
+
+    /// Like `try_next`, but does not mark the snapshot observed. So, a
+    /// following call to `next` or `try_next` will return the same result.
+    pub fn peek(&mut self) -> Option<Result<T, Closed>> {
+        let mut prev = self.prev.clone();
+        Self::poll_now(&self.change, &mut prev).map(|update| update.map(|v| v.into_value()))
+    }
+
+    /// True if `next` would resolve without waiting. That is, if there is an
+    /// unobserved update or the Eventual is closed. Does not mark anything
+    /// observed.
+    pub fn has_changed(&mut self) -> bool {
+        let mut prev = self.prev.clone();
+        Self::poll_now(&self.change, &mut prev).is_some()
+    }
+
+    /// Resolves once the writer has closed or finalized the Eventual, whether
+    /// or not this reader has observed the final value.
+    pub fn closed(&self) -> impl 'static + Future<Output = ()> {
+        let state = self.change.unsubscribe_from.clone();
+        async move {
+            loop {
+                let notified = state.closed.notified();
+                if state.is_closed() {
+                    return;
+                }
+                notified.await;
+            }
+        }
+    }
+
+    /// Why the Eventual was closed, or None if it is not closed. This is
+    /// available as soon as the Eventual is closed, which may be before this
+    /// reader observes `Closed`.
+    pub fn closed_reason(&self) -> Option<ClosedReason> {
+        self.change.unsubscribe_from.closed_reason.get().cloned()
+    }
+
+    /// Like `next`, but closes `writer` with the reason this reader's Eventual
+    /// was closed. This is how combinators propagate the reason.
+    pub(crate) async fn next_or_close<U>(&mut self, writer: &EventualWriter<U>) -> Result<T, Closed>
+    where
+        U: LocalValue,
+    {
+        self.next_snapshot_or_close(writer)
+            .await
+            .map(|v| v.into_value())
+    }
+
+    /// Like `next_or_close`, but resolves with the snapshot so that its
+    /// metadata can be carried downstream.
+    pub(crate) async fn next_snapshot_or_close<U>(
+        &mut self,
+        writer: &EventualWriter<U>,
+    ) -> Result<Versioned<T>, Closed>
+    where
+        U: LocalValue,
+    {
+        let next = poll_fn(|cx| self.poll_versioned(cx)).await;
+        if next.is_err() {
+            writer.close_after(self);
+        }
+        next
+    }
+
+    /// Like `next_or_close`, but also resolves with the time at which the
+    /// snapshot was written, to be carried downstream.
+    pub(crate) async fn next_timestamped_or_close<U>(
+        &mut self,
+        writer: &EventualWriter<U>,
+    ) -> Result<(T, Instant), Closed>
+    where
+        U: LocalValue,
+    {
+        self.next_snapshot_or_close(writer).await.map(|v| {
+            let written_at = v.written_at;
+            (v.into_value(), written_at)
+        })
+    }
+
+    /// Resolves with the next write, even if it is redundant with the
+    /// previous observation. Used by combinators which care about when
+    /// writes happen rather than about the value.
+    pub(crate) async fn next_write(&mut self) -> Result<Versioned<T>, Closed> {
+        poll_fn(|cx| self.change.poll_write(&mut self.prev, cx)).await
+    }
+
+    /// The dedup strategy of the Eventual being read.
+    pub(crate) fn dedup(&self) -> Arc<dyn Dedup<T>> {
+        self.change.unsubscribe_from.dedup.clone()
+    }
+
     pub(crate) fn new(state: Arc<SharedState<T>>) -> Self {
         let change = state.subscribe();
 
         EventualReader { change, prev: None }
     }
 
+    // TODO: This is currently checking for a pushed value, but that will require
+    // eg: map() to run in separate tasks. It might be desirable to have this poll
+    // the future that would produce values. But... that may be very complex. A
+    // refactor may be necessary.
+    fn poll_versioned(&mut self, cx: &mut Context<'_>) -> Poll<Result<Versioned<T>, Closed>> {
+        self.change.poll(&mut self.prev, cx)
+    }
+
+    // Checks for an update without waiting. Any waker registered by a
+    // previous poll is replaced, which is fine since polling requires
+    // &mut self.
+    fn poll_now(
+        change: &ChangeReader<T>,
+        prev: &mut Option<Result<Versioned<T>, Closed>>,
+    ) -> Option<Result<Versioned<T>, Closed>> {
+        let mut cx = Context::from_waker(noop_waker_ref());
+        match change.poll_now(prev, &mut cx) {
+            Poll::Ready(update) => Some(update),
+            Poll::Pending => None,
+        }
+    }
+
     /// This function is pretty tricky. Be sure you know what you are doing.
     pub(crate) fn force_dirty(&mut self) {
         self.prev = None;
-        self.change.unsubscribe_from.notify_one(&self.change.change);
     }
 }
 
 // The cloned reader resumes from the same state as the source.
 impl<T> Clone for EventualReader<T>
 where
-    T: Value,
+    T: LocalValue,
 {
     fn clone(&self) -> Self {
         Self {
             prev: self.prev.clone(),
-            // This ends up being pre-notified with the latest value, but that's
-            // ok because it still gets de-duped when checking against
-            // self.prev. This is nice, because otherwise all the locking is
-            // really hard (impossible?) to get right.
+            // Since snapshots are not pushed to subscribers, the new
+            // subscription simply compares the latest snapshot against the
+            // same self.prev on its first poll.
             //
             // The thing to make sure we get right is that the reader is effectively
             // in the same state as the reader it's being cloned from. Assuming
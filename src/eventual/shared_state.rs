@@ -1,33 +1,63 @@
 use futures::channel::oneshot::Sender;
+use slab::Slab;
 use std::{
-    collections::HashSet,
     ops::Deref,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering::Relaxed},
+        Arc, Mutex, OnceLock,
+    },
 };
+use tokio::sync::Notify;
+
+use crate::{ClosedReason, PanicPolicy};
 
 use super::{
-    change::{Change, ChangeReader, ChangeValNoWake},
+    ack::Ack,
+    change::{Change, ChangeReader, ChangeValNoWake, Versioned},
+    dedup::Dedup,
+    idle::{self, IdleScope},
     *,
 };
 
 pub struct SharedState<T> {
-    // This takes a cue from the .NET event implementation
-    // which bets that:
-    //    Typically the number of subscribers is relatively small
-    //    Modifying the subscriber list is less frequent than posting events
-    // These taken together advocate for a snapshot-at-time style of concurrency
-    // which makes snapshotting very cheap but updates expensive.
+    // Subscribers are kept in a slab so that subscribing and unsubscribing are
+    // O(1), even with many thousands of short lived readers. Each ChangeReader
+    // remembers its key in order to remove itself on drop.
     //
-    // There is maybe a case for some kind of persistent immutable data structure
-    // here (finally, for once in my career...). `im` on crates.io is a candidate.
-    // (Lookout though, `im` uses MPL license which is incompatible with MIT)
-    // It would probably require a lot of subscribers before paying off - which
-    // itself requires heavy linear costs. So, I am skeptical still.
-    pub subscribers: Mutex<Arc<HashSet<Change<T>>>>,
+    // Wakers are taken from the subscribers while holding the lock, but woken
+    // after it is released so that woken tasks can never contend with (or
+    // deadlock on) the subscriber list.
+    pub subscribers: Mutex<Slab<Change>>,
     pub last_write: Mutex<ChangeValNoWake<T>>,
+    // The version of the most recent write. Only modified while holding the
+    // last_write lock, but atomic so that it can be read without it.
+    pub generation: AtomicU64,
+    pub dedup: Arc<dyn Dedup<T>>,
+    // Notified when the writer closes or finalizes the Eventual.
+    pub closed: Notify,
+    // What the stage writing to the Eventual does when it panics. See also
+    // `Eventual::with_panic_policy`.
+    panic_policy: AtomicU8,
+    // Set once, when the Eventual is closed.
+    pub closed_reason: OnceLock<ClosedReason>,
+    // Only created once a writer asks for it, so that subscribing costs
+    // nothing extra otherwise.
+    subscriber_count: OnceLock<SubscriberCount>,
+    // The IdleScope which was current when the Eventual was created. Readers
+    // subscribed outside of any scope belong to this one.
+    scope: Option<IdleScope>,
+    // The span of the task which writes to the Eventual, or of the Eventual
+    // itself once it is named. See also `Eventual::named`.
+    #[cfg(feature = "tracing")]
+    span: OnceLock<tracing::Span>,
     writer_notify: Option<Sender<()>>,
 }
 
+struct SubscriberCount {
+    writer: Mutex<EventualWriter<usize>>,
+    eventual: Eventual<usize>,
+}
+
 impl<T> Drop for SharedState<T> {
     fn drop(&mut self) {
         if let Some(notify) = self.writer_notify.take() {
@@ -39,44 +69,123 @@ impl<T> Drop for SharedState<T> {
     }
 }
 
+impl<T> SharedState<T> {
+    // Must be called after releasing the subscribers lock, since the write
+    // wakes the readers of the count. The count is read while holding the
+    // writer's lock, so whichever change publishes last writes the current
+    // count and a stale one can never overwrite it.
+    pub fn publish_subscriber_count(&self) {
+        if let Some(count) = self.subscriber_count.get() {
+            let mut writer = count.writer.lock().unwrap();
+            let len = self.subscribers.lock().unwrap().len();
+            writer.write(len);
+        }
+    }
+
+    /// The span for this Eventual, created with `kind` on first use.
+    #[cfg(feature = "tracing")]
+    pub fn span(&self, kind: &'static str) -> &tracing::Span {
+        self.span.get_or_init(|| {
+            tracing::info_span!(target: "eventuals", "stage", kind, name = tracing::field::Empty)
+        })
+    }
+
+    /// Run `f` in the span of this Eventual, if it has one.
+    #[cfg(feature = "tracing")]
+    pub fn in_span(&self, f: impl FnOnce()) {
+        match self.span.get() {
+            Some(span) => span.in_scope(f),
+            None => f(),
+        }
+    }
+}
+
 impl<T> SharedState<T>
 where
-    T: Value,
+    T: LocalValue,
 {
-    pub fn new(writer_notify: Sender<()>) -> Self {
+    pub fn new(writer_notify: Sender<()>, dedup: Arc<dyn Dedup<T>>) -> Self {
         Self {
-            subscribers: Mutex::new(Arc::new(HashSet::new())),
+            subscribers: Mutex::new(Slab::new()),
             last_write: Mutex::new(ChangeValNoWake::None),
+            generation: AtomicU64::new(0),
+            dedup,
+            closed: Notify::new(),
+            panic_policy: AtomicU8::new(PanicPolicy::default() as u8),
+            closed_reason: OnceLock::new(),
+            subscriber_count: OnceLock::new(),
+            scope: idle::current(),
+            #[cfg(feature = "tracing")]
+            span: OnceLock::new(),
             writer_notify: Some(writer_notify),
         }
     }
 
-    pub fn notify_all(&self) {
-        let snapshot = {
+    pub fn panic_policy(&self) -> PanicPolicy {
+        PanicPolicy::from_u8(self.panic_policy.load(Relaxed))
+    }
+
+    pub fn set_panic_policy(&self, policy: PanicPolicy) {
+        self.panic_policy.store(policy as u8, Relaxed);
+    }
+
+    /// The latest snapshot, if any.
+    pub fn snapshot(&self) -> Option<Versioned<T>> {
+        match self.last_write.lock().unwrap().deref() {
+            ChangeValNoWake::None => None,
+            ChangeValNoWake::Value(t) => Some(t.clone()),
+            ChangeValNoWake::Finalized(t) => t.clone(),
+        }
+    }
+
+    /// Wake every subscriber which is waiting on a new value, attaching
+    /// `acks` to every subscriber. Returns true if there were any subscribers.
+    pub fn notify_all(&self, acks: &[Ack]) -> bool {
+        let (wakers, any): (Vec<_>, _) = {
             let lock = self.subscribers.lock().unwrap();
-            lock.deref().clone()
+            let wakers = lock.iter().filter_map(|(_, c)| c.notify(acks)).collect();
+            (wakers, !lock.is_empty())
         };
-        for subscriber in snapshot.iter() {
-            self.notify_one(subscriber)
+        // Wake outside of the lock, since waking may run arbitrary code.
+        for waker in wakers {
+            waker.wake();
         }
+        any
+    }
+
+    /// True once the writer has closed or finalized the Eventual.
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self.last_write.lock().unwrap().deref(),
+            ChangeValNoWake::Finalized(_)
+        )
     }
 
-    pub fn notify_one(&self, subscriber: &Change<T>) {
-        subscriber.set_value(&self.last_write);
+    /// An Eventual which is updated with the number of subscribers whenever
+    /// it changes.
+    pub fn subscriber_count(&self) -> Eventual<usize> {
+        let subscribers = self.subscribers.lock().unwrap();
+        let count = self.subscriber_count.get_or_init(|| {
+            let (mut writer, eventual) = Eventual::new();
+            writer.write(subscribers.len());
+            SubscriberCount {
+                writer: Mutex::new(writer),
+                eventual,
+            }
+        });
+        count.eventual.clone()
     }
 
     pub fn subscribe(self: Arc<Self>) -> ChangeReader<T> {
-        let change: Change<T> = Change::new();
-        {
-            let mut lock = self.subscribers.lock().unwrap();
-            let mut updated: HashSet<_> = lock.deref().deref().clone();
-            updated.insert(change.clone());
-            *lock = Arc::new(updated);
-        }
-        // Must notify AFTER it's in the subscriber list to avoid missing updates.
-        self.notify_one(&change);
+        let change = Change::new(idle::current().or_else(|| self.scope.clone()));
+        let key = {
+            let mut subscribers = self.subscribers.lock().unwrap();
+            subscribers.insert(change.clone())
+        };
+        self.publish_subscriber_count();
         ChangeReader {
             change,
+            key,
             unsubscribe_from: self,
         }
     }
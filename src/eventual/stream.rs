@@ -0,0 +1,111 @@
+//! Adapters between eventuals and futures::Stream and futures::Sink. These
+//! are opt-in because the semantics differ in ways that are easy to miss. A
+//! Stream is a sequence of distinct items, each of which is meant to be
+//! observed, whereas an Eventual is a value which is eventually consistent
+//! and may skip intermediate snapshots. See also the comment on
+//! EventualReader.
+
+use super::*;
+use crate::{runtime, ClosedReason, Spawner};
+use futures::{Sink, Stream, StreamExt};
+
+/// A Stream of the snapshots observed by an EventualReader.
+/// See `EventualReader::into_latest_stream`.
+pub struct LatestStream<T> {
+    reader: EventualReader<T>,
+}
+
+impl<T> Stream for LatestStream<T>
+where
+    T: LocalValue,
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.reader.next())
+            .poll(cx)
+            .map(|next| next.ok())
+    }
+}
+
+impl<T> EventualReader<T>
+where
+    T: LocalValue,
+{
+    /// Convert this reader into a Stream which yields the same snapshots
+    /// that `next` would, and ends when the Eventual is closed. Like `next`,
+    /// the stream only yields the latest snapshot when polled, so a slow
+    /// consumer skips intermediate values rather than buffering them. Items
+    /// are never yielded twice in a row.
+    pub fn into_latest_stream(self) -> LatestStream<T> {
+        LatestStream { reader: self }
+    }
+}
+
+impl<T> Eventual<T>
+where
+    T: Value,
+{
+    /// Create an Eventual which is updated with the items of `stream`. The
+    /// stream is consumed as fast as it produces items, but readers only
+    /// observe the latest one. The Eventual is closed when the stream ends,
+    /// and the stream is dropped when every reader is dropped.
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: 'static + Stream<Item = T> + Send,
+        T: PartialEq,
+    {
+        Self::from_stream_on(&runtime::DefaultSpawner, stream)
+    }
+
+    /// Like `from_stream`, but runs the task with a specific Spawner.
+    pub fn from_stream_on<Sp, S>(spawner: &Sp, stream: S) -> Self
+    where
+        Sp: Spawner + ?Sized,
+        S: 'static + Stream<Item = T> + Send,
+        T: PartialEq,
+    {
+        Eventual::spawn_on(spawner, |mut writer| async move {
+            let mut stream = Box::pin(stream);
+            while let Some(item) = stream.next().await {
+                writer.write(item);
+            }
+            Err(Closed)
+        })
+    }
+}
+
+/// Each item sent is written to the Eventual, so readers only observe the
+/// latest one. Sending never waits. Once the Eventual is closed, or every
+/// reader is dropped, the sink returns Err(Closed). Closing the sink closes
+/// the Eventual for every clone of the writer. Note that `EventualWriter::close`
+/// shadows `SinkExt::close`, so the latter must be called as
+/// `SinkExt::close(&mut writer)`.
+impl<T> Sink<T> for EventualWriter<T>
+where
+    T: LocalValue,
+{
+    type Error = Closed;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.is_closed() {
+            Poll::Ready(Err(Closed))
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.write(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.close_with_reason(ClosedReason::WriterDropped);
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,122 @@
+use super::*;
+use crate::{
+    error::{catch_panic, panic_message},
+    runtime, ClosedReason, Spawner, Timer,
+};
+use futures::never::Never;
+use std::time::Duration;
+use tokio::select;
+use writer::TaskGuard;
+
+/// Configures how `Eventual::spawn_supervised` restarts a producer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// How long to wait before the first restart.
+    pub initial_backoff: Duration,
+    /// The backoff doubles with each consecutive restart, up to this. A
+    /// producer which ran for longer than this before failing is considered
+    /// to have recovered, so the backoff starts over from `initial_backoff`.
+    pub max_backoff: Duration,
+    /// Give up and close the Eventual after this many restarts. None
+    /// restarts forever.
+    pub max_restarts: Option<u32>,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            max_restarts: None,
+        }
+    }
+}
+
+impl<T> Eventual<T>
+where
+    T: Value,
+{
+    /// Like `spawn`, but for long lived producers which may fail. Whenever
+    /// the future returned by `factory` completes or panics, `factory` is
+    /// called again to create a new one after a backoff. The last written
+    /// value remains visible in the meantime. If the producer closes the
+    /// Eventual itself, such as with `EventualWriter::close_with_error`, it is
+    /// not restarted.
+    ///
+    /// Also returns the number of times the producer has been restarted.
+    pub fn spawn_supervised<F, Fut>(factory: F, policy: RestartPolicy) -> (Self, Eventual<u32>)
+    where
+        T: PartialEq,
+        F: 'static + Send + FnMut(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        Self::spawn_supervised_on(
+            &runtime::DefaultSpawner,
+            runtime::default_timer(),
+            factory,
+            policy,
+        )
+    }
+
+    /// Like `spawn_supervised`, but runs the task with a specific Spawner and
+    /// waits out the backoff with a specific Timer.
+    pub fn spawn_supervised_on<S, F, Fut>(
+        spawner: &S,
+        timer: Arc<dyn Timer>,
+        mut factory: F,
+        policy: RestartPolicy,
+    ) -> (Self, Eventual<u32>)
+    where
+        T: PartialEq,
+        S: Spawner + ?Sized,
+        F: 'static + Send + FnMut(EventualWriter<T>) -> Fut,
+        Fut: Future<Output = Result<Never, Closed>> + Send,
+    {
+        let (writer, eventual) = Eventual::new();
+        let (mut restarts_writer, restarts) = Eventual::new();
+        restarts_writer.write(0);
+        let guard = TaskGuard::new(&writer);
+
+        let task = async move {
+            // Held between restarts, so that the Eventual stays open.
+            let writer = &writer;
+            let supervise = async move {
+                let mut backoff = policy.initial_backoff;
+                let mut restarts = 0;
+                loop {
+                    let started = timer.now();
+                    let reason = match catch_panic(|| factory(writer.clone())).await {
+                        Ok(Err(Closed)) => ClosedReason::WriterDropped,
+                        Err(panic) => ClosedReason::Panicked(panic_message(&panic)),
+                    };
+                    if writer.is_closed() {
+                        return;
+                    }
+                    if policy.max_restarts.is_some_and(|max| restarts >= max) {
+                        writer.close_with_reason(reason);
+                        return;
+                    }
+                    if timer.now() - started > policy.max_backoff {
+                        backoff = policy.initial_backoff;
+                    }
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(target: "eventuals", ?reason, ?backoff, "restarting");
+                    timer.sleep(backoff).await;
+                    backoff = (backoff * 2).min(policy.max_backoff);
+                    restarts += 1;
+                    restarts_writer.write(restarts);
+                }
+            };
+            select!(
+                _ = writer.closed() => {}
+                _ = supervise => {}
+            );
+            guard.complete();
+        };
+        #[cfg(feature = "tracing")]
+        let task =
+            tracing::Instrument::instrument(task, eventual.span("eventuals::spawn_supervised"));
+        runtime::spawn_on(spawner, task);
+        (eventual, restarts)
+    }
+}
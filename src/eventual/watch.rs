@@ -0,0 +1,91 @@
+//! Conversions between eventuals and `tokio::sync::watch`, which has similar
+//! "latest value" semantics.
+
+use super::*;
+use crate::{runtime, ClosedReason, IntoReader, Spawner};
+use tokio::{select, sync::watch};
+
+impl<T> Eventual<T>
+where
+    T: Value,
+{
+    /// Create an Eventual which is updated with the values sent to a watch
+    /// channel, starting with the current one. The Eventual is closed with
+    /// `ClosedReason::UpstreamClosed` when the watch Sender is dropped, and
+    /// the Receiver is dropped once every reader of the Eventual is dropped.
+    pub fn from_watch(receiver: watch::Receiver<T>) -> Self
+    where
+        T: PartialEq,
+    {
+        Self::from_watch_on(&runtime::DefaultSpawner, receiver)
+    }
+
+    /// Like `from_watch`, but runs the task with a specific Spawner.
+    pub fn from_watch_on<S>(spawner: &S, mut receiver: watch::Receiver<T>) -> Self
+    where
+        T: PartialEq,
+        S: Spawner + ?Sized,
+    {
+        Eventual::spawn_on(spawner, |mut writer| async move {
+            loop {
+                let value = receiver.borrow_and_update().clone();
+                writer.write(value);
+                if receiver.changed().await.is_err() {
+                    writer.close_with_reason(ClosedReason::UpstreamClosed);
+                    return Err(Closed);
+                }
+            }
+        })
+    }
+
+    /// Create a watch channel which is updated with the snapshots of this
+    /// Eventual. The value is None until the Eventual has a value. The Sender
+    /// is dropped when the Eventual is closed, and the Eventual is no longer
+    /// read once every Receiver is dropped.
+    pub fn to_watch(&self) -> watch::Receiver<Option<T>> {
+        self.to_watch_on(&runtime::DefaultSpawner)
+    }
+
+    /// Like `to_watch`, but runs the task with a specific Spawner.
+    pub fn to_watch_on<S>(&self, spawner: &S) -> watch::Receiver<Option<T>>
+    where
+        S: Spawner + ?Sized,
+    {
+        let mut reader = self.subscribe();
+        let dedup = reader.dedup();
+        let (sender, receiver) = watch::channel(self.value_immediate());
+        runtime::spawn_on(spawner, async move {
+            let forward = async {
+                while let Ok(value) = reader.next().await {
+                    // The first snapshot is usually the initial value.
+                    let is_duplicate = match sender.borrow().as_ref() {
+                        Some(prev) => dedup.is_duplicate(prev, &value),
+                        None => false,
+                    };
+                    if is_duplicate {
+                        continue;
+                    }
+                    if sender.send(Some(value)).is_err() {
+                        return;
+                    }
+                }
+            };
+            select!(
+                _ = sender.closed() => {}
+                _ = forward => {}
+            );
+        });
+        receiver
+    }
+}
+
+impl<T> IntoReader for watch::Receiver<T>
+where
+    T: Value + PartialEq,
+{
+    type Output = T;
+    #[inline]
+    fn into_reader(self) -> EventualReader<Self::Output> {
+        Eventual::from_watch(self).subscribe()
+    }
+}
@@ -1,59 +1,436 @@
 use futures::{channel::oneshot::Receiver, future::Shared};
 
-use super::{change::ChangeValNoWake, *};
-use crate::error::Closed;
+use super::{
+    ack::{self, Ack},
+    change::{ChangeValNoWake, Versioned},
+    *,
+};
+use crate::{
+    error::{panic_message, Panic},
+    ClosedReason, PanicPolicy,
+};
 use futures::FutureExt;
 use std::{
+    error::Error,
     mem,
-    ops::DerefMut,
-    sync::{Arc, Weak},
+    ops::{Deref, DerefMut},
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering::{Relaxed, SeqCst},
+        },
+        Arc, Weak,
+    },
+    time::Instant,
 };
 
+/// Describes the effect of a conditional write, such as
+/// `EventualWriter::update` or `EventualWriter::compare_and_write`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WriteOutcome {
+    /// A new value was written. This is false if the write was declined or the
+    /// new value was a duplicate of the previous one.
+    pub changed: bool,
+    /// At least one subscriber was notified of the new value.
+    pub notified: bool,
+}
+
+/// The write end of an Eventual. Writers may be cloned so that several tasks
+/// can write to the same Eventual. The Eventual is finalized when the last
+/// clone is dropped.
 pub struct EventualWriter<T>
 where
-    T: Value,
+    T: LocalValue,
+{
+    inner: Arc<WriterInner<T>>,
+}
+
+// Shared by all clones of a writer. Dropping this is what closes the Eventual.
+struct WriterInner<T>
+where
+    T: LocalValue,
 {
     state: Weak<SharedState<T>>,
     closed: Shared<Receiver<()>>,
+    // Set once a TaskGuard watches over the writer. See TaskGuard.
+    guarded: AtomicBool,
 }
 
-impl<T> Drop for EventualWriter<T>
+impl<T> Drop for WriterInner<T>
 where
-    T: Value,
+    T: LocalValue,
 {
     fn drop(&mut self) {
-        let _ignore = self.write_private(Err(Closed));
+        // A writer dropped while its task unwinds leaves closing the Eventual
+        // to the guard, so that the panic is reported rather than
+        // ClosedReason::WriterDropped.
+        if self.guarded.load(Relaxed) && std::thread::panicking() {
+            return;
+        }
+        self.write_private(Transition::Close(ClosedReason::WriterDropped));
+    }
+}
+
+impl<T> Clone for EventualWriter<T>
+where
+    T: LocalValue,
+{
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
     }
 }
 
 impl<T> EventualWriter<T>
 where
-    T: Value,
+    T: LocalValue,
 {
     pub(crate) fn new(state: &Arc<SharedState<T>>, closed: Receiver<()>) -> Self {
         Self {
-            state: Arc::downgrade(state),
-            closed: closed.shared(),
+            inner: Arc::new(WriterInner {
+                state: Arc::downgrade(state),
+                closed: closed.shared(),
+                guarded: AtomicBool::new(false),
+            }),
         }
     }
 
+    /// Resolves once all readers of the Eventual have been dropped. This works
+    /// for every clone of the writer.
     pub fn closed(&self) -> impl 'static + Future + Send + Unpin {
-        self.closed.clone()
+        self.inner.closed.clone()
+    }
+
+    /// An Eventual which is updated with the number of readers whenever it
+    /// changes. This allows a producer to do work only while anyone is
+    /// interested, for example by waiting for the count to become non-zero
+    /// before starting and pausing whenever it drops back to zero. Closes
+    /// once all readers have been dropped for good.
+    ///
+    /// Like any Eventual, the count only promises to deliver the latest
+    /// value. Changes in quick succession may be observed as one, so a reader
+    /// being replaced (1 → 0 → 1) may never be seen as 0. Producers should
+    /// not rely on observing every transition.
+    pub fn subscribers(&self) -> Eventual<usize> {
+        match self.inner.state.upgrade() {
+            Some(state) => state.subscriber_count(),
+            None => Eventual::from_value(0),
+        }
     }
 
     pub fn write(&mut self, value: T) {
-        self.write_private(Ok(value))
+        self.write_at(value, Instant::now())
+    }
+
+    /// Like `write`, but the returned future resolves once every reader of the
+    /// Eventual has observed this value (or a newer one) and waited for the
+    /// next, including the readers downstream of combinators such as `map`,
+    /// `join`, and `pipe`. This allows waiting until a change is live, such as
+    /// a new configuration. Stages which delay their writes, such as
+    /// `throttle`, only acknowledge having observed the value. Readers which
+    /// are not being polled hold up the acknowledgement until they are
+    /// dropped. If the Eventual is closed, this resolves immediately.
+    pub fn write_acked(&mut self, value: T) -> impl 'static + Future<Output = ()> + Send + Unpin {
+        let (ack, acked) = Ack::new();
+        self.inner
+            .write_private_acked(Transition::Write(value, Instant::now()), Some(ack));
+        acked.map(|_| ())
+    }
+
+    /// Like `write`, but records `written_at` as the time of the write rather
+    /// than now. Combinators use this to carry the timestamp of their source.
+    pub(crate) fn write_at(&mut self, value: T, written_at: Instant) {
+        self.inner
+            .write_private(Transition::Write(value, written_at))
+    }
+
+    /// Close the Eventual without waiting for every clone of the writer to be
+    /// dropped. The last written value (if any) becomes the final value.
+    /// Writes from other clones are ignored after this.
+    pub fn close(self) {
+        self.inner
+            .write_private(Transition::Close(ClosedReason::WriterDropped))
     }
 
-    fn write_private(&mut self, value: Result<T, Closed>) {
-        if let Some(state) = self.state.upgrade() {
+    /// Close the Eventual because of an error. Readers observe `Closed`, and
+    /// `closed_reason` returns `ClosedReason::Error` with `error`. The last
+    /// written value (if any) becomes the final value.
+    pub fn close_with_error<E>(self, error: E)
+    where
+        E: 'static + Error + Send + Sync,
+    {
+        let reason = ClosedReason::Error(Arc::new(error));
+        self.inner.write_private(Transition::Close(reason))
+    }
+
+    /// Atomically write a final value and close the Eventual.
+    /// See also `close`.
+    pub fn finalize(self, value: T) {
+        self.inner.write_private(Transition::Finalize(value))
+    }
+
+    /// Return to the state of having no value, as though nothing had been
+    /// written yet. `Eventual::value` waits for the next write afterward.
+    /// Readers are not notified of the reset, and will not observe the next
+    /// write if it is equal to the value they last observed. This has no
+    /// effect once the Eventual is closed.
+    pub fn reset(&mut self) {
+        self.inner.write_private(Transition::Reset)
+    }
+
+    /// Atomically replace the current value (if any) with the one returned by
+    /// `f`. If `f` returns None, or a duplicate of the current value according
+    /// to the dedup strategy of the Eventual, nothing is written and
+    /// subscribers are not notified.
+    ///
+    /// `f` runs while holding the lock on the value of the Eventual, so it
+    /// must not read the same Eventual (such as with `value_immediate`) or
+    /// write to it, which would deadlock. If `f` panics nothing is written,
+    /// and the panic is resumed once the lock has been released so that the
+    /// Eventual remains usable.
+    pub fn update<F>(&mut self, f: F) -> WriteOutcome
+    where
+        F: FnOnce(Option<&T>) -> Option<T>,
+    {
+        let state = match self.inner.state.upgrade() {
+            Some(state) => state,
+            None => return WriteOutcome::default(),
+        };
+        {
             // See also b045e23a-f445-456f-a686-7e80de621cf2
-            {
-                let mut prev = state.last_write.lock().unwrap();
+            let mut prev = state.last_write.lock().unwrap();
+            let current = match prev.deref() {
+                ChangeValNoWake::None => None,
+                ChangeValNoWake::Value(current) => Some(&*current.value),
+                // Closed by another clone of this writer.
+                ChangeValNoWake::Finalized(_) => return WriteOutcome::default(),
+            };
+            // Catching the panic here, rather than letting it unwind through
+            // the guard, keeps the lock from being poisoned.
+            let next = panic::catch_unwind(AssertUnwindSafe(|| {
+                f(current)
+                    .filter(|value| !current.is_some_and(|c| state.dedup.is_duplicate(c, value)))
+            }));
+            let value = match next {
+                Ok(Some(value)) => value,
+                Ok(None) => return WriteOutcome::default(),
+                Err(panic) => {
+                    drop(prev);
+                    panic::resume_unwind(panic);
+                }
+            };
+            let version = state.generation.fetch_add(1, SeqCst) + 1;
+            let value = Arc::new(value);
+            let written_at = Instant::now();
+            *prev = ChangeValNoWake::Value(Versioned {
+                value,
+                version,
+                written_at,
+            });
+        }
+        #[cfg(feature = "tracing")]
+        state.in_span(|| {
+            let version = state.generation.load(SeqCst);
+            tracing::trace!(target: "eventuals", version, "write");
+        });
+        WriteOutcome {
+            changed: true,
+            notified: state.notify_all(&ack::carried()),
+        }
+    }
+
+    /// The span of the Eventual, created with `kind` if there is none yet.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn span(&self, kind: &'static str) -> tracing::Span {
+        match self.inner.state.upgrade() {
+            Some(state) => state.span(kind).clone(),
+            None => tracing::Span::none(),
+        }
+    }
+
+    pub(crate) fn close_with_reason(&self, reason: ClosedReason) {
+        self.inner.write_private(Transition::Close(reason))
+    }
+
+    /// True if the Eventual has been closed, or if it has been dropped.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.inner
+            .state
+            .upgrade()
+            .is_none_or(|state| state.is_closed())
+    }
+
+    /// Close the Eventual because `upstream` was closed, propagating the
+    /// reason. Used by combinators.
+    pub(crate) fn close_after<U>(&self, upstream: &EventualReader<U>)
+    where
+        U: LocalValue,
+    {
+        let reason = upstream
+            .closed_reason()
+            .unwrap_or(ClosedReason::WriterDropped)
+            .downstream();
+        self.inner.write_private(Transition::Close(reason))
+    }
+
+    /// Apply the PanicPolicy of the Eventual to a panic caught while
+    /// producing values for this writer. Returns Ok if the stage should
+    /// continue with the next value, which is only possible if `can_skip`.
+    pub(crate) fn handle_panic(&self, panic: Panic, can_skip: bool) -> Result<(), Closed> {
+        handle_panic(&self.inner.state, panic, can_skip)
+    }
+
+    /// Write `new` only if the current value is equal to `expected`, where
+    /// None means that no value has been written yet.
+    pub fn compare_and_write(&mut self, expected: Option<&T>, new: T) -> WriteOutcome
+    where
+        T: PartialEq,
+    {
+        self.update(|current| if current == expected { Some(new) } else { None })
+    }
+}
+
+/// Held by the task which drives a writer for `Eventual::spawn`. Reports a
+/// panic of the task, and closes the Eventual with `ClosedReason::Cancelled`
+/// if the task is dropped before it completes. The guard does not count as a
+/// writer, so the task dropping its writer still closes the Eventual.
+pub(crate) struct TaskGuard<T>
+where
+    T: LocalValue,
+{
+    state: Weak<SharedState<T>>,
+    closed: Shared<Receiver<()>>,
+    completed: bool,
+}
+
+impl<T> TaskGuard<T>
+where
+    T: LocalValue,
+{
+    pub fn new(writer: &EventualWriter<T>) -> Self {
+        writer.inner.guarded.store(true, Relaxed);
+        Self {
+            state: writer.inner.state.clone(),
+            closed: writer.inner.closed.clone(),
+            completed: false,
+        }
+    }
+
+    /// See `EventualWriter::closed`.
+    pub fn closed(&self) -> impl 'static + Future + Send + Unpin {
+        self.closed.clone()
+    }
+
+    /// See `EventualWriter::handle_panic`.
+    pub fn handle_panic(&self, panic: Panic, can_skip: bool) -> Result<(), Closed> {
+        handle_panic(&self.state, panic, can_skip)
+    }
+
+    pub fn complete(mut self) {
+        self.completed = true;
+    }
+}
+
+impl<T> Drop for TaskGuard<T>
+where
+    T: LocalValue,
+{
+    fn drop(&mut self) {
+        // Once the task completes, the writer may only have been left open by
+        // being dropped while panicking. See WriterInner::drop.
+        let reason = if self.completed {
+            ClosedReason::WriterDropped
+        } else {
+            ClosedReason::Cancelled
+        };
+        transition(&self.state, Transition::Close(reason), None);
+    }
+}
 
-                if let Ok(value) = value {
-                    *prev = ChangeValNoWake::Value(value);
-                } else {
+fn handle_panic<T>(state: &Weak<SharedState<T>>, panic: Panic, can_skip: bool) -> Result<(), Closed>
+where
+    T: LocalValue,
+{
+    let policy = match state.upgrade() {
+        Some(state) => state.panic_policy(),
+        None => PanicPolicy::default(),
+    };
+    if policy == PanicPolicy::Skip && can_skip {
+        return Ok(());
+    }
+    let reason = ClosedReason::Panicked(panic_message(&panic));
+    transition(state, Transition::Close(reason), None);
+    if policy == PanicPolicy::Propagate {
+        std::panic::resume_unwind(panic);
+    }
+    Err(Closed)
+}
+
+// The changes a writer can make to the shared state.
+enum Transition<T> {
+    Write(T, Instant),
+    Finalize(T),
+    Close(ClosedReason),
+    Reset,
+}
+
+impl<T> WriterInner<T>
+where
+    T: LocalValue,
+{
+    fn write_private(&self, transition: Transition<T>) {
+        self.write_private_acked(transition, None)
+    }
+
+    fn write_private_acked(&self, t: Transition<T>, ack: Option<Ack>) {
+        transition(&self.state, t, ack)
+    }
+}
+
+fn transition<T>(state: &Weak<SharedState<T>>, transition: Transition<T>, ack: Option<Ack>)
+where
+    T: LocalValue,
+{
+    if let Some(state) = state.upgrade() {
+        let closing;
+        // See also b045e23a-f445-456f-a686-7e80de621cf2
+        {
+            let mut prev = state.last_write.lock().unwrap();
+
+            // Once finalized, the value never changes. This happens when
+            // one clone of the writer closes the Eventual before the others
+            // are dropped.
+            if let ChangeValNoWake::Finalized(_) = prev.deref() {
+                return;
+            }
+
+            closing = matches!(transition, Transition::Finalize(_) | Transition::Close(_));
+            match transition {
+                Transition::Write(value, written_at) => {
+                    let version = state.generation.fetch_add(1, SeqCst) + 1;
+                    let value = Arc::new(value);
+                    *prev = ChangeValNoWake::Value(Versioned {
+                        value,
+                        version,
+                        written_at,
+                    });
+                }
+                Transition::Finalize(value) => {
+                    let version = state.generation.fetch_add(1, SeqCst) + 1;
+                    let value = Arc::new(value);
+                    let written_at = Instant::now();
+                    *prev = ChangeValNoWake::Finalized(Some(Versioned {
+                        value,
+                        version,
+                        written_at,
+                    }));
+                    let _ = state.closed_reason.set(ClosedReason::WriterDropped);
+                }
+                Transition::Close(reason) => {
+                    let _ = state.closed_reason.set(reason);
                     match mem::replace(prev.deref_mut(), ChangeValNoWake::None) {
                         ChangeValNoWake::None => {
                             *prev = ChangeValNoWake::Finalized(None);
@@ -64,8 +441,32 @@ where
                         ChangeValNoWake::Finalized(_) => unreachable!(),
                     }
                 }
+                Transition::Reset => {
+                    // There is nothing new to observe, so there is no need
+                    // to notify. Readers waiting on a value continue to
+                    // wait, and the rest find nothing when polled.
+                    *prev = ChangeValNoWake::None;
+                    return;
+                }
             }
-            state.notify_all();
         }
+        if closing {
+            state.closed.notify_waiters();
+        }
+        // Writes made while processing an acknowledged write carry its
+        // Ack downstream.
+        let mut acks = ack::carried();
+        acks.extend(ack);
+        state.notify_all(&acks);
+        #[cfg(feature = "tracing")]
+        state.in_span(|| {
+            if closing {
+                let reason = state.closed_reason.get();
+                tracing::debug!(target: "eventuals", ?reason, "closed");
+            } else {
+                let version = state.generation.load(SeqCst);
+                tracing::trace!(target: "eventuals", version, "write");
+            }
+        });
     }
 }
@@ -1,7 +1,9 @@
 mod eventual;
 pub use eventual::*;
 pub mod error;
-pub use error::Closed;
+pub use error::{Closed, ClosedReason, PanicPolicy};
+pub mod runtime;
+pub use runtime::{Spawner, Timer};
 mod combinators;
 pub use combinators::*;
 
@@ -12,5 +14,19 @@ pub trait IntoReader {
     fn into_reader(self) -> EventualReader<Self::Output>;
 }
 
-pub trait Value: 'static + Send + Clone + Eq {}
-impl<T> Value for T where T: 'static + Send + Clone + Eq {}
+/// The equivalent of IntoReader for LocalEventual.
+#[cfg(feature = "tokio-runtime")]
+pub trait LocalIntoReader {
+    type Output: LocalValue;
+    fn into_reader(self) -> LocalEventualReader<Self::Output>;
+}
+
+// Sync is required because a single snapshot of the value is shared by every
+// reader rather than being cloned for each of them when it is written.
+pub trait Value: LocalValue + Send + Sync {}
+impl<T> Value for T where T: LocalValue + Send + Sync {}
+
+/// A value which may be used with LocalEventual. Unlike Value, this does not
+/// require Send.
+pub trait LocalValue: 'static + Clone {}
+impl<T> LocalValue for T where T: 'static + Clone {}
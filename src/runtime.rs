@@ -0,0 +1,196 @@
+//! Eventuals need to spawn tasks and wait on timers. By default this is done
+//! with tokio (behind the `tokio-runtime` feature), but any executor may be
+//! used. Prefer passing a Spawner or Timer explicitly, with
+//! `Eventual::spawn_on` or the other `_on` variants, such as `throttle_on` or
+//! `Eventual::spawn_supervised_on`. The defaults set here are process-wide,
+//! and are only the fallback for everything which is not given one.
+
+use futures::future::BoxFuture;
+use std::{
+    future::Future,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+/// Runs futures to completion in the background.
+pub trait Spawner: Send + Sync {
+    fn spawn(&self, future: BoxFuture<'static, ()>);
+
+    /// Like `spawn`, but with a name for the task which may be shown by tools
+    /// such as tokio-console. The name is ignored by default.
+    #[inline]
+    fn spawn_named(&self, name: &str, future: BoxFuture<'static, ()>) {
+        let _ = name;
+        self.spawn(future)
+    }
+}
+
+/// Allows using a closure as a Spawner. Eg: `|f| pool.spawn_ok(f)`
+impl<F> Spawner for F
+where
+    F: Send + Sync + Fn(BoxFuture<'static, ()>),
+{
+    #[inline]
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        self(future)
+    }
+}
+
+/// Produces futures which resolve at some point in time.
+pub trait Timer: Send + Sync {
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()>;
+
+    /// The current time according to this Timer. Deadlines passed to
+    /// `sleep_until` are computed from it, so a Timer with its own clock
+    /// (such as tokio's, which may be paused) must override this.
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    #[inline]
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        self.sleep_until(self.now() + duration)
+    }
+}
+
+impl<F> Timer for F
+where
+    F: Send + Sync + Fn(Instant) -> BoxFuture<'static, ()>,
+{
+    #[inline]
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        self(deadline)
+    }
+}
+
+/// Spawns onto the tokio runtime of the calling context, like
+/// `tokio::spawn`. This panics outside of a tokio runtime.
+#[cfg(feature = "tokio-runtime")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TokioSpawner;
+
+#[cfg(feature = "tokio-runtime")]
+impl Spawner for TokioSpawner {
+    #[inline]
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        tokio::spawn(future);
+    }
+
+    #[cfg(all(tokio_unstable, feature = "tracing"))]
+    fn spawn_named(&self, name: &str, future: BoxFuture<'static, ()>) {
+        let _ = tokio::task::Builder::new().name(name).spawn(future);
+    }
+}
+
+/// Spawns onto a specific tokio runtime.
+#[cfg(feature = "tokio-runtime")]
+impl Spawner for tokio::runtime::Handle {
+    #[inline]
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        tokio::runtime::Handle::spawn(self, future);
+    }
+
+    #[cfg(all(tokio_unstable, feature = "tracing"))]
+    fn spawn_named(&self, name: &str, future: BoxFuture<'static, ()>) {
+        let _ = tokio::task::Builder::new()
+            .name(name)
+            .spawn_on(future, self);
+    }
+}
+
+/// Uses tokio::time. This requires the tokio runtime to have the time driver
+/// enabled. The time is taken from tokio's clock, so this follows
+/// `tokio::time::pause` and `tokio::time::advance`.
+#[cfg(feature = "tokio-runtime")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TokioTimer;
+
+#[cfg(feature = "tokio-runtime")]
+impl Timer for TokioTimer {
+    #[inline]
+    fn sleep_until(&self, deadline: Instant) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep_until(deadline.into()))
+    }
+
+    #[inline]
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    #[inline]
+    fn now(&self) -> Instant {
+        tokio::time::Instant::now().into_std()
+    }
+}
+
+static DEFAULT_SPAWNER: RwLock<Option<Arc<dyn Spawner>>> = RwLock::new(None);
+static DEFAULT_TIMER: RwLock<Option<Arc<dyn Timer>>> = RwLock::new(None);
+
+/// Set the Spawner used by `Eventual::spawn` and by all combinators unless
+/// one is given explicitly. If this is never called, tokio is used when the
+/// `tokio-runtime` feature is enabled.
+pub fn set_default_spawner<S>(spawner: S)
+where
+    S: 'static + Spawner,
+{
+    *DEFAULT_SPAWNER.write().unwrap() = Some(Arc::new(spawner));
+}
+
+/// Set the Timer used by combinators such as `timer` and `throttle`, as
+/// opposed to `timer_on` and `throttle_on`. If this is never called, tokio is
+/// used when the `tokio-runtime` feature is enabled.
+pub fn set_default_timer<T>(timer: T)
+where
+    T: 'static + Timer,
+{
+    *DEFAULT_TIMER.write().unwrap() = Some(Arc::new(timer));
+}
+
+/// Dispatches to the Spawner set with `set_default_spawner`, or tokio.
+pub(crate) struct DefaultSpawner;
+
+impl Spawner for DefaultSpawner {
+    fn spawn(&self, future: BoxFuture<'static, ()>) {
+        let spawner = DEFAULT_SPAWNER.read().unwrap().clone();
+        match spawner {
+            Some(spawner) => spawner.spawn(future),
+            #[cfg(feature = "tokio-runtime")]
+            None => TokioSpawner.spawn(future),
+            #[cfg(not(feature = "tokio-runtime"))]
+            None => panic!("No Spawner. Call eventuals::runtime::set_default_spawner first."),
+        }
+    }
+
+    fn spawn_named(&self, name: &str, future: BoxFuture<'static, ()>) {
+        let spawner = DEFAULT_SPAWNER.read().unwrap().clone();
+        match spawner {
+            Some(spawner) => spawner.spawn_named(name, future),
+            #[cfg(feature = "tokio-runtime")]
+            None => TokioSpawner.spawn_named(name, future),
+            #[cfg(not(feature = "tokio-runtime"))]
+            None => panic!("No Spawner. Call eventuals::runtime::set_default_spawner first."),
+        }
+    }
+}
+
+pub(crate) fn spawn_on<S, F>(spawner: &S, future: F)
+where
+    S: Spawner + ?Sized,
+    F: 'static + Send + Future<Output = ()>,
+{
+    // Tasks belong to the IdleScope they were spawned from.
+    spawner.spawn(Box::pin(crate::eventual::scoped(Box::pin(future))))
+}
+
+/// The Timer set with `set_default_timer`, or tokio.
+pub(crate) fn default_timer() -> Arc<dyn Timer> {
+    let timer = DEFAULT_TIMER.read().unwrap().clone();
+    match timer {
+        Some(timer) => timer,
+        #[cfg(feature = "tokio-runtime")]
+        None => Arc::new(TokioTimer),
+        #[cfg(not(feature = "tokio-runtime"))]
+        None => panic!("No Timer. Call eventuals::runtime::set_default_timer first."),
+    }
+}
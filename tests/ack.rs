@@ -0,0 +1,81 @@
+use eventuals::*;
+use futures::FutureExt;
+use std::time::Duration;
+use tokio::test;
+use tokio::time::{sleep, timeout};
+
+#[test]
+async fn acked_after_pipeline_applies_value() {
+    let (mut writer, source) = Eventual::<u32>::new();
+    let (mut applied_writer, applied) = Eventual::<u32>::new();
+    let doubled = source.map(|v| async move {
+        sleep(Duration::from_millis(10)).await;
+        v * 2
+    });
+    let _pipe = doubled.pipe(move |v| applied_writer.write(v + 1));
+
+    writer.write_acked(1).await;
+    assert_eq!(applied.value_immediate(), Some(3));
+
+    writer.write_acked(5).await;
+    assert_eq!(applied.value_immediate(), Some(11));
+}
+
+#[test]
+async fn acked_immediately_without_readers() {
+    let (mut writer, eventual) = Eventual::<u32>::new();
+    writer.write_acked(1).await;
+    assert_eq!(eventual.value_immediate(), Some(1));
+}
+
+#[test]
+async fn waits_for_every_reader() {
+    let (mut writer, eventual) = Eventual::<u32>::new();
+    let mut reader = eventual.subscribe();
+
+    let mut acked = writer.write_acked(1);
+    assert!((&mut acked).now_or_never().is_none());
+
+    // Observing the value is not enough. The reader must wait again.
+    assert_eq!(reader.next().await, Ok(1));
+    assert!((&mut acked).now_or_never().is_none());
+
+    let next = reader.next();
+    assert!(timeout(Duration::from_millis(1), next).await.is_err());
+    acked.await;
+}
+
+#[test]
+async fn dropping_a_reader_on_another_thread_acks() {
+    let (mut writer, eventual) = Eventual::<u32>::new();
+    let mut reader = eventual.subscribe();
+    let (send, receive) = futures::channel::oneshot::channel();
+    // The task observes the value and hands its reader off without waiting.
+    let _task = Eventual::<u32>::spawn(move |_writer| async move {
+        assert_eq!(reader.next().await, Ok(1));
+        let _ = send.send(reader);
+        futures::future::pending().await
+    });
+
+    let acked = writer.write_acked(1);
+    let reader = receive.await.unwrap();
+    std::thread::spawn(move || drop(reader)).join().unwrap();
+    timeout(Duration::from_secs(1), acked).await.unwrap();
+}
+
+#[test]
+async fn values_with_pending_acks_are_visible_without_waiting() {
+    let (mut writer, eventual) = Eventual::<u32>::new();
+    let mut reader = eventual.subscribe();
+    let acked = writer.write_acked(1);
+
+    assert!(reader.has_changed());
+    assert_eq!(reader.peek(), Some(Ok(1)));
+    assert!(reader.has_changed());
+    assert_eq!(reader.try_next(), Some(Ok(1)));
+    assert!(!reader.has_changed());
+    assert_eq!(reader.try_next(), None);
+
+    drop(reader);
+    timeout(Duration::from_secs(1), acked).await.unwrap();
+}
@@ -0,0 +1,60 @@
+use eventuals::*;
+use std::{thread, time::Duration};
+
+#[test]
+fn blocking_next_waits_for_write() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut reader = eventual.subscribe();
+
+    let handle = thread::spawn(move || {
+        let first = reader.blocking_next();
+        let second = reader.blocking_next();
+        (first, second)
+    });
+
+    writer.write(1);
+    thread::sleep(Duration::from_millis(10));
+    drop(writer);
+
+    assert_eq!(handle.join().unwrap(), (Ok(1), Err(Closed)));
+}
+
+#[test]
+fn blocking_next_timeout_expires() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut reader = eventual.subscribe();
+
+    assert_eq!(reader.blocking_next_timeout(Duration::from_millis(5)), None);
+    writer.write(1);
+    assert_eq!(
+        reader.blocking_next_timeout(Duration::from_millis(5)),
+        Some(Ok(1))
+    );
+}
+
+#[test]
+fn blocking_value_waits_for_write() {
+    let (mut writer, eventual) = Eventual::new();
+
+    let handle = thread::spawn(move || eventual.blocking_value());
+    thread::sleep(Duration::from_millis(1));
+    writer.write("value");
+
+    assert_eq!(handle.join().unwrap(), Ok("value"));
+}
+
+#[tokio::test]
+#[should_panic(expected = "async context")]
+async fn blocking_panics_in_async_context() {
+    let eventual = Eventual::from_value(1);
+    let _ = eventual.blocking_value();
+}
+
+#[tokio::test]
+async fn blocking_next_in_spawn_blocking() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut reader = eventual.subscribe();
+    let handle = tokio::task::spawn_blocking(move || reader.blocking_next());
+    writer.write(1);
+    assert_eq!(handle.await.unwrap(), Ok(1));
+}
@@ -0,0 +1,60 @@
+use eventuals::*;
+use futures::future::BoxFuture;
+use std::{error::Error, fmt};
+use tokio::test;
+
+#[derive(Debug)]
+struct Failed;
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed")
+    }
+}
+
+impl Error for Failed {}
+
+#[test]
+async fn errors_propagate_through_combinators() {
+    let (mut a_writer, a) = Eventual::new();
+    let (mut b_writer, b) = Eventual::new();
+    let joined = join((a.clone(), b)).map(|(a, b)| async move { a + b });
+    a_writer.write(1);
+    b_writer.write(2);
+    assert_eq!(joined.value().await, Ok(3));
+
+    a_writer.close_with_error(Failed);
+    let mut reader = joined.subscribe();
+    assert_eq!(reader.next().await, Ok(3));
+    assert_eq!(reader.next().await, Err(Closed));
+    match reader.closed_reason() {
+        Some(ClosedReason::Error(e)) => {
+            assert_eq!(e.to_string(), "failed");
+            // The same error is shared rather than copied.
+            assert_eq!(a.closed_reason(), Some(ClosedReason::Error(e)));
+        }
+        reason => panic!("{:?}", reason),
+    }
+}
+
+#[test]
+async fn dropped_writers_close_downstream_as_upstream_closed() {
+    let (writer, source) = Eventual::<u32>::new();
+    let mapped = source.clone().map(|v| async move { v });
+    let throttled = mapped.throttle(std::time::Duration::from_millis(1));
+    drop(writer);
+    assert_eq!(throttled.value().await, Err(Closed));
+    assert_eq!(source.closed_reason(), Some(ClosedReason::WriterDropped));
+    assert_eq!(
+        throttled.closed_reason(),
+        Some(ClosedReason::UpstreamClosed)
+    );
+}
+
+#[test]
+async fn dropped_tasks_are_cancelled() {
+    let never_run = |f: BoxFuture<'static, ()>| drop(f);
+    let eventual = Eventual::<u32>::spawn_on(&never_run, |_writer| futures::future::pending());
+    assert_eq!(eventual.value().await, Err(Closed));
+    assert_eq!(eventual.closed_reason(), Some(ClosedReason::Cancelled));
+}
@@ -0,0 +1,153 @@
+use eventuals::{dedup::*, *};
+use futures::poll;
+use std::{sync::Arc, task::Poll};
+use tokio::test;
+
+#[test]
+async fn floats_can_be_observed() {
+    let (mut writer, eventual) = Eventual::<f64>::new();
+    let mut reader = eventual.subscribe();
+    writer.write(1.5);
+    assert_eq!(reader.next().await, Ok(1.5));
+    writer.write(1.5);
+    assert_eq!(poll!(reader.next()), Poll::Pending);
+    writer.write(f64::NAN);
+    assert!(reader.next().await.unwrap().is_nan());
+}
+
+#[test]
+async fn no_dedup_observes_equal_writes() {
+    let (mut writer, eventual) = Eventual::with_dedup(NoDedup);
+    let mut reader = eventual.subscribe();
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(1));
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(1));
+    assert_eq!(poll!(reader.next()), Poll::Pending);
+    assert!(writer.update(|_| Some(1)).changed);
+}
+
+#[test]
+async fn by_ptr_compares_allocations() {
+    let (mut writer, eventual) = Eventual::with_dedup(ByPtr);
+    let mut reader = eventual.subscribe();
+    let a = Arc::new(vec![1, 2, 3]);
+    writer.write(a.clone());
+    assert_eq!(reader.next().await, Ok(a.clone()));
+    writer.write(a.clone());
+    assert_eq!(poll!(reader.next()), Poll::Pending);
+    writer.write(Arc::new(vec![1, 2, 3]));
+    assert_eq!(reader.next().await, Ok(a));
+}
+
+#[test]
+async fn by_hash_and_closures() {
+    let (mut writer, eventual) = Eventual::with_dedup(ByHash);
+    let mut reader = eventual.subscribe();
+    writer.write("a".to_owned());
+    assert_eq!(reader.next().await, Ok("a".to_owned()));
+    writer.write("a".to_owned());
+    assert_eq!(poll!(reader.next()), Poll::Pending);
+
+    // Only observe changes of at least 10.
+    let (mut writer, eventual) =
+        Eventual::with_dedup(|prev: &u32, next: &u32| prev.abs_diff(*next) < 10);
+    let mut reader = eventual.subscribe();
+    writer.write(0);
+    assert_eq!(reader.next().await, Ok(0));
+    writer.write(5);
+    assert_eq!(poll!(reader.next()), Poll::Pending);
+    writer.write(15);
+    assert_eq!(reader.next().await, Ok(15));
+}
+
+#[test]
+async fn threshold_compares_with_the_received_value() {
+    // 5 is within the threshold of 0 and skipped, but 12 is not. 12 must not
+    // be compared against 5, which the reader never received.
+    let (mut writer, eventual) =
+        Eventual::with_dedup(|prev: &u32, next: &u32| prev.abs_diff(*next) < 10);
+    let mut reader = eventual.subscribe();
+    writer.write(0);
+    assert_eq!(reader.next().await, Ok(0));
+    writer.write(5);
+    assert_eq!(poll!(reader.next()), Poll::Pending);
+    writer.write(12);
+    assert_eq!(reader.next().await, Ok(12));
+}
+
+// Deliberately not PartialEq.
+#[derive(Clone, Debug)]
+struct Opaque(u32);
+
+#[test]
+async fn values_need_not_be_comparable() {
+    let (mut writer, eventual) = Eventual::with_dedup(NoDedup);
+    let mapped = eventual
+        .map_with_dedup(NoDedup, |v: Opaque| async move { Opaque(v.0 + 1) })
+        .throttle(std::time::Duration::from_millis(1));
+    writer.write(Opaque(1));
+    assert_eq!(mapped.value().await.unwrap().0, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn map_with_dedup_applies_to_the_output() {
+    let (mut writer, eventual) = Eventual::<u32>::new();
+    let (mut calls_writer, calls) = Eventual::new();
+    let cached = Arc::new(vec![0u8; 1024]);
+    let output = cached.clone();
+    let mapped = eventual.map_with_dedup(ByPtr, move |v| {
+        calls_writer.write(v);
+        let output = output.clone();
+        async move { output }
+    });
+    let mut reader = mapped.subscribe();
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(cached.clone()));
+    writer.write(2);
+    let mut calls = calls.subscribe();
+    while calls.next().await != Ok(2) {}
+    // The same allocation was written again, so there is nothing to observe.
+    let next = tokio::time::timeout(std::time::Duration::from_secs(1), reader.next());
+    assert!(next.await.is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn join_with_dedup_applies_to_the_output() {
+    let (mut a_writer, a) = Eventual::<u32>::new();
+    let (mut b_writer, b) = Eventual::<u32>::new();
+    // Only observe changes to the sum.
+    let joined = join_with_dedup((a, b), |prev: &(u32, u32), next: &(u32, u32)| {
+        prev.0 + prev.1 == next.0 + next.1
+    });
+    let mut reader = joined.subscribe();
+    a_writer.write(1);
+    b_writer.write(2);
+    assert_eq!(reader.next().await, Ok((1, 2)));
+    a_writer.write(2);
+    b_writer.write(1);
+    let next = tokio::time::timeout(std::time::Duration::from_secs(1), reader.next());
+    assert!(next.await.is_err());
+    a_writer.write(3);
+    assert_eq!(reader.next().await, Ok((3, 1)));
+}
+
+#[test]
+async fn pass_through_stages_keep_the_dedup_of_the_source() {
+    let (mut writer, eventual) = Eventual::with_dedup(NoDedup);
+    let mut reader = eventual.init_with(0).subscribe();
+    assert_eq!(reader.next().await, Ok(0));
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(1));
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(1));
+}
+
+#[test]
+async fn spawn_with_dedup() {
+    let eventual = Eventual::spawn_with_dedup(NoDedup, |mut writer| async move {
+        writer.write(Opaque(7));
+        futures::future::pending().await
+    });
+    assert_eq!(eventual.value().await.unwrap().0, 7);
+}
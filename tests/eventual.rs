@@ -1,6 +1,14 @@
 use eventuals::*;
 use futures::poll;
-use std::{sync::Arc, task::Poll, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+    task::Poll,
+    thread,
+    time::Duration,
+};
 use tokio::{join, test, time::sleep};
 
 #[test]
@@ -147,4 +155,354 @@ async fn init_with_has_value() {
     assert_eq!(values.next().await, Ok(10));
 }
 
+#[test]
+async fn versions_increase_with_writes() {
+    let (mut writer, eventual) = Eventual::new();
+    assert_eq!(eventual.value_immediate_versioned(), None);
+    let mut read_0 = eventual.subscribe();
+    writer.write(5);
+    let (value, first) = read_0.next_versioned().await.unwrap();
+    assert_eq!(value, 5);
+    assert_eq!(eventual.value_immediate_versioned(), Some((5, first)));
+
+    writer.write(10);
+    let (value, second) = read_0.next_versioned().await.unwrap();
+    assert_eq!(value, 10);
+    assert!(second > first);
+
+    // Writing an equal value is a new version, but is still de-duplicated.
+    writer.write(10);
+    let (_, third) = eventual.value_immediate_versioned().unwrap();
+    assert!(third > second);
+    assert_eq!(Poll::Pending, poll!(read_0.next_versioned()));
+
+    drop(writer);
+    assert_eq!(read_0.next_versioned().await, Err(Closed));
+    assert_eq!(eventual.value_immediate_versioned(), Some((10, third)));
+}
+
+#[test]
+async fn update_patches_current_value() {
+    let (mut writer, eventual) = Eventual::<u32>::new();
+
+    // Declining to write leaves the eventual without a value.
+    let outcome = writer.update(|prev| prev.map(|v| v + 1));
+    assert_eq!(outcome, WriteOutcome::default());
+    assert_eq!(eventual.value_immediate(), None);
+
+    // There are no subscribers yet, so nobody is notified.
+    let outcome = writer.update(|prev| Some(prev.copied().unwrap_or(0) + 1));
+    assert_eq!(
+        outcome,
+        WriteOutcome {
+            changed: true,
+            notified: false
+        }
+    );
+
+    let mut read_0 = eventual.subscribe();
+    let outcome = writer.update(|prev| prev.map(|v| v + 1));
+    assert_eq!(
+        outcome,
+        WriteOutcome {
+            changed: true,
+            notified: true
+        }
+    );
+    assert_eq!(read_0.next().await, Ok(2));
+
+    // Writing an equal value is not a change.
+    let outcome = writer.update(|prev| prev.copied());
+    assert_eq!(outcome, WriteOutcome::default());
+    assert_eq!(Poll::Pending, poll!(read_0.next()));
+}
+
+#[test]
+async fn panic_in_update_leaves_eventual_usable() {
+    let (mut writer, eventual) = Eventual::<u32>::new();
+    writer.write(1);
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        writer.update(|_| panic!("update"));
+    }));
+    assert!(panicked.is_err());
+    // Nothing was written and the value can still be read and written.
+    assert_eq!(eventual.value_immediate(), Some(1));
+    assert!(writer.update(|prev| prev.map(|v| v + 1)).changed);
+    assert_eq!(eventual.value().await, Ok(2));
+}
+
+#[test]
+async fn compare_and_write_only_writes_expected() {
+    let (mut writer, eventual) = Eventual::<u32>::new();
+
+    assert!(!writer.compare_and_write(Some(&1), 2).changed);
+    assert!(writer.compare_and_write(None, 1).changed);
+    assert!(!writer.compare_and_write(None, 3).changed);
+    assert!(writer.compare_and_write(Some(&1), 2).changed);
+    assert_eq!(eventual.value_immediate(), Some(2));
+}
+
+#[test]
+async fn cloned_writers_close_with_last_clone() {
+    let (mut writer_0, eventual) = Eventual::new();
+    let mut writer_1 = writer_0.clone();
+    let mut read_0 = eventual.subscribe();
+
+    writer_0.write(1);
+    assert_eq!(read_0.next().await, Ok(1));
+    writer_1.write(2);
+    assert_eq!(read_0.next().await, Ok(2));
+
+    drop(writer_0);
+    assert_eq!(Poll::Pending, poll!(read_0.next()));
+    writer_1.write(3);
+    assert_eq!(read_0.next().await, Ok(3));
+
+    drop(writer_1);
+    assert_eq!(read_0.next().await, Err(Closed));
+}
+
+#[test]
+async fn cloned_writers_observe_closed() {
+    let (writer_0, eventual) = Eventual::<u32>::new();
+    let writer_1 = writer_0.clone();
+    let closed_0 = writer_0.closed();
+    let closed_1 = writer_1.closed();
+    drop(eventual);
+    closed_0.await;
+    closed_1.await;
+}
+
+#[test]
+async fn close_ignores_other_writers() {
+    let (mut writer_0, eventual) = Eventual::new();
+    let mut writer_1 = writer_0.clone();
+    let mut read_0 = eventual.subscribe();
+    writer_0.write(1);
+    writer_0.close();
+    writer_1.write(2);
+    assert_eq!(read_0.next().await, Ok(1));
+    assert_eq!(read_0.next().await, Err(Closed));
+    assert_eq!(eventual.value_immediate(), Some(1));
+}
+
+#[test]
+async fn finalize_writes_final_value() {
+    let (writer, eventual) = Eventual::new();
+    let mut read_0 = eventual.subscribe();
+    writer.finalize(5);
+    assert_eq!(read_0.next().await, Ok(5));
+    assert_eq!(read_0.next().await, Err(Closed));
+}
+
+#[test]
+async fn reset_waits_for_next_write() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut read_0 = eventual.subscribe();
+    writer.write(1);
+    assert_eq!(read_0.next().await, Ok(1));
+
+    // An unobserved value is discarded by reset.
+    writer.write(2);
+    writer.reset();
+    assert_eq!(eventual.value_immediate(), None);
+    assert_eq!(Poll::Pending, poll!(read_0.next()));
+
+    let mut value = eventual.value();
+    assert_eq!(Poll::Pending, poll!(&mut value));
+    writer.write(3);
+    assert_eq!(value.await, Ok(3));
+    assert_eq!(read_0.next().await, Ok(3));
+
+    // Closing after a reset has no final value.
+    writer.reset();
+    drop(writer);
+    assert_eq!(read_0.next().await, Err(Closed));
+    assert_eq!(eventual.value().await, Err(Closed));
+}
+
+#[derive(PartialEq, Eq)]
+struct CountClones(Ptr<AtomicUsize>);
+
+impl Clone for CountClones {
+    fn clone(&self) -> Self {
+        self.0.fetch_add(1, SeqCst);
+        Self(self.0.clone())
+    }
+}
+
+#[test]
+async fn writes_do_not_clone() {
+    let clones = Ptr::new(AtomicUsize::new(0));
+    let (mut writer, eventual) = Eventual::new();
+    let mut readers: Vec<_> = (0..100).map(|_| eventual.subscribe()).collect();
+    writer.write(CountClones(clones.clone()));
+    writer.write(CountClones(clones.clone()));
+    assert_eq!(clones.load(SeqCst), 0);
+
+    // Clones happen once per observation instead.
+    readers[0].next().await.unwrap();
+    assert_eq!(clones.load(SeqCst), 1);
+    readers.clear();
+}
+
+#[test]
+async fn references_do_not_clone() {
+    let clones = Ptr::new(AtomicUsize::new(0));
+    let (mut writer, eventual) = Eventual::new();
+    let mut reader = eventual.subscribe();
+    assert_eq!(eventual.with_value(|_| ()), None);
+    writer.write(CountClones(clones.clone()));
+
+    let snapshot = reader.next_ref().await.unwrap();
+    assert!(eventual.with_value(|v| v == &*snapshot).unwrap());
+    writer.write(CountClones(Ptr::new(AtomicUsize::new(0))));
+    // The snapshot is unaffected by later writes.
+    assert!(snapshot.0 == clones);
+    assert!(reader.next_ref().await.unwrap().0 != clones);
+    assert_eq!(clones.load(SeqCst), 0);
+
+    drop(writer);
+    assert!(reader.next_ref().await.is_err());
+}
+
+#[test]
+async fn readers_on_threads_observe_final_write() {
+    let (mut writer, eventual) = Eventual::new();
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let mut reader = eventual.subscribe();
+            thread::spawn(move || {
+                let mut last = 0;
+                while let Ok(value) = reader.blocking_next() {
+                    assert!(value > last);
+                    last = value;
+                }
+                last
+            })
+        })
+        .collect();
+    tokio::task::spawn_blocking(move || {
+        for i in 1..=10_000 {
+            writer.write(i);
+        }
+    })
+    .await
+    .unwrap();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), 10_000);
+    }
+}
+
+#[test]
+async fn try_next_peek_and_has_changed() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut reader = eventual.subscribe();
+    assert!(!reader.has_changed());
+    assert_eq!(reader.peek(), None);
+    assert_eq!(reader.try_next(), None);
+
+    writer.write(1);
+    assert!(reader.has_changed());
+    assert_eq!(reader.peek(), Some(Ok(1)));
+    assert_eq!(reader.peek(), Some(Ok(1)));
+    assert_eq!(reader.try_next(), Some(Ok(1)));
+    assert!(!reader.has_changed());
+    assert_eq!(reader.try_next(), None);
+
+    // Redundant writes are not changes.
+    writer.write(1);
+    assert!(!reader.has_changed());
+
+    // try_next does not interfere with a waiting reader.
+    writer.write(2);
+    assert_eq!(reader.try_next(), Some(Ok(2)));
+    let (_, value) = join!(async { writer.write(3) }, reader.next());
+    assert_eq!(value, Ok(3));
+
+    drop(writer);
+    assert!(reader.has_changed());
+    assert_eq!(reader.peek(), Some(Err(Closed)));
+    assert_eq!(reader.try_next(), Some(Err(Closed)));
+    assert_eq!(reader.try_next(), Some(Err(Closed)));
+}
+
+#[test]
+async fn writer_observes_subscriber_count() {
+    let (writer, eventual) = Eventual::<u32>::new();
+    let mut counts = writer.subscribers().subscribe();
+    assert_eq!(counts.next().await, Ok(0));
+
+    let reader = eventual.subscribe();
+    assert_eq!(counts.next().await, Ok(1));
+    let other = reader.clone();
+    assert_eq!(counts.next().await, Ok(2));
+    drop(reader);
+    drop(other);
+    assert_eq!(counts.next().await, Ok(0));
+    // Watching the count does not count as a subscriber.
+    assert_eq!(eventual.subscriber_count(), 0);
+
+    let _reader = eventual.subscribe();
+    assert_eq!(counts.next().await, Ok(1));
+    drop(eventual);
+    drop(_reader);
+    assert_eq!(counts.next().await, Ok(0));
+    assert_eq!(counts.next().await, Err(Closed));
+}
+
+#[test]
+async fn subscriber_count_settles_on_the_latest_count() {
+    let (writer, eventual) = Eventual::<u32>::new();
+    let counts = writer.subscribers();
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let eventual = eventual.clone();
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    drop(eventual.subscribe());
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let _reader = eventual.subscribe();
+    assert_eq!(counts.value().await, Ok(1));
+    assert_eq!(counts.value_immediate(), Some(1));
+}
+
+#[test]
+async fn reader_observes_close() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut reader = eventual.subscribe();
+    let closed = reader.closed();
+    writer.write(1);
+    assert_eq!(poll!(Box::pin(reader.closed())), Poll::Pending);
+    let (_, ()) = join!(async { writer.finalize(2) }, closed);
+    // The final value is still observed after closed resolves.
+    assert_eq!(reader.next().await, Ok(2));
+    reader.closed().await;
+
+    let (writer, eventual) = Eventual::<u32>::new();
+    let reader = eventual.subscribe();
+    let (_, ()) = join!(async move { drop(writer) }, reader.closed());
+}
+
+#[test]
+async fn weak_eventual_does_not_keep_eventual_alive() {
+    let (mut writer, eventual) = Eventual::new();
+    let weak = eventual.downgrade();
+    assert_eq!(weak.value_immediate(), None);
+    writer.write(1);
+    assert_eq!(weak.value_immediate(), Some(1));
+    assert_eq!(weak.upgrade().unwrap().value().await, Ok(1));
+
+    drop(eventual);
+    writer.closed().await;
+    assert!(weak.upgrade().is_none());
+    assert_eq!(weak.value_immediate(), None);
+}
+
 // TODO: Test that closed is received twice in a row rather than getting stuck.
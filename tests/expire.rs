@@ -0,0 +1,58 @@
+use eventuals::*;
+use std::time::Duration;
+use tokio::{test, time::sleep};
+
+const TTL: Duration = Duration::from_millis(20);
+
+#[test]
+async fn expire_after_resets() {
+    let (mut writer, source) = Eventual::new();
+    let expiring = source.expire_after(TTL, OnExpire::Reset);
+    writer.write(1);
+    assert_eq!(expiring.value().await, Ok(1));
+    sleep(TTL * 3).await;
+    assert_eq!(expiring.value_immediate(), None);
+
+    // Writing the same value again counts as a write.
+    writer.write(1);
+    assert_eq!(expiring.value().await, Ok(1));
+}
+
+#[test]
+async fn expire_after_closes() {
+    let (mut writer, source) = Eventual::new();
+    let expiring = source.expire_after(TTL, OnExpire::Close);
+    let mut reader = expiring.subscribe();
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(1));
+    assert_eq!(reader.next().await, Err(Closed));
+    assert_eq!(expiring.closed_reason(), Some(ClosedReason::Expired));
+}
+
+#[test]
+async fn liveness_follows_writes() {
+    let (mut writer, source) = Eventual::new();
+    let mut live = source.liveness(TTL).subscribe();
+    assert_eq!(live.next().await, Ok(false));
+    writer.write(1);
+    assert_eq!(live.next().await, Ok(true));
+    assert_eq!(live.next().await, Ok(false));
+    writer.write(1);
+    assert_eq!(live.next().await, Ok(true));
+    drop(writer);
+    assert_eq!(live.next().await, Ok(false));
+    assert_eq!(live.next().await, Err(Closed));
+}
+
+#[test]
+async fn expire_after_close_reaches_downstream_stages() {
+    let (mut writer, source) = Eventual::<u32>::new();
+    let mapped = source
+        .expire_after(TTL, OnExpire::Close)
+        .map(|v| async move { v + 1 });
+    let mut reader = mapped.subscribe();
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(2));
+    assert_eq!(reader.next().await, Err(Closed));
+    assert_eq!(mapped.closed_reason(), Some(ClosedReason::Expired));
+}
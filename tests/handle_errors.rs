@@ -26,7 +26,7 @@ async fn basic() {
     let even_numbers = handle_errors(validated, move |err: u32| {
         println!("Err: {}", err);
         let mut errors = errors_writer.lock().unwrap();
-        errors.push(err.clone());
+        errors.push(err);
         notify_write1.notify_one()
     });
     let _pipe = even_numbers
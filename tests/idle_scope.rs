@@ -0,0 +1,48 @@
+use eventuals::*;
+use std::time::Duration;
+use tokio::test;
+use tokio::time::sleep;
+
+#[test]
+async fn scope_ignores_other_pipelines() {
+    // This pipeline is never idle, so the global idle would never complete.
+    let (mut writer, reader) = Eventual::<u8>::new();
+    let _stuck = reader.map(|_v| futures::future::pending::<u8>());
+    writer.write(1);
+
+    let scope = IdleScope::new();
+    let (mut scoped_writer, source) = Eventual::<u32>::new();
+    let mapped = {
+        let _enter = scope.enter();
+        source.map(move |v| async move {
+            sleep(Duration::from_millis(10)).await;
+            v + 1
+        })
+    };
+    scoped_writer.write(1);
+    scope.idle().await;
+    assert_eq!(mapped.value_immediate(), Some(2));
+
+    scoped_writer.write(5);
+    scope.idle().await;
+    assert_eq!(mapped.value_immediate(), Some(6));
+}
+
+#[test]
+async fn wrap_includes_tasks_spawned_directly() {
+    let scope = IdleScope::new();
+    let (mut writer, source) = Eventual::<u32>::new();
+    let (mut doubled_writer, doubled) = Eventual::<u32>::new();
+    tokio::spawn(scope.wrap(async move {
+        let mut reader = source.subscribe();
+        while let Ok(v) = reader.next().await {
+            sleep(Duration::from_millis(10)).await;
+            doubled_writer.write(v * 2);
+        }
+    }));
+    // Let the task subscribe before writing.
+    tokio::task::yield_now().await;
+    writer.write(1);
+    scope.idle().await;
+    assert_eq!(doubled.value_immediate(), Some(2));
+}
@@ -0,0 +1,65 @@
+use eventuals::*;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering::SeqCst},
+    Arc,
+};
+use tokio::test;
+
+struct Running(Arc<AtomicUsize>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, SeqCst);
+    }
+}
+
+#[test]
+async fn lazy_runs_only_while_subscribed() {
+    let starts = Arc::new(AtomicUsize::new(0));
+    let running = Arc::new(AtomicUsize::new(0));
+
+    let scope = IdleScope::new();
+    let _enter = scope.enter();
+    let (starts_p, running_p) = (starts.clone(), running.clone());
+    let eventual = Eventual::lazy(move |mut writer| {
+        let start = starts_p.fetch_add(1, SeqCst) + 1;
+        running_p.fetch_add(1, SeqCst);
+        let running = Running(running_p.clone());
+        async move {
+            let _running = running;
+            writer.write(start);
+            futures::future::pending().await
+        }
+    });
+    scope.idle().await;
+    assert_eq!(starts.load(SeqCst), 0);
+    assert_eq!(eventual.value_immediate(), None);
+
+    let mut reader = eventual.subscribe();
+    assert_eq!(reader.next().await, Ok(1));
+    assert_eq!(running.load(SeqCst), 1);
+
+    // The producer stops with the last reader, but the value remains.
+    drop(reader);
+    scope.idle().await;
+    assert_eq!(running.load(SeqCst), 0);
+    assert_eq!(eventual.value_immediate(), Some(1));
+
+    // Subscribing again restarts the producer.
+    let mut reader = eventual.subscribe();
+    assert_eq!(reader.next().await, Ok(1));
+    assert_eq!(reader.next().await, Ok(2));
+    assert_eq!(running.load(SeqCst), 1);
+    assert_eq!(starts.load(SeqCst), 2);
+}
+
+#[test]
+async fn lazy_closes_when_producer_completes() {
+    let eventual = Eventual::lazy(|mut writer| async move {
+        writer.write(1);
+        Err(Closed)
+    });
+    let mut reader = eventual.subscribe();
+    assert_eq!(reader.next().await, Ok(1));
+    assert_eq!(reader.next().await, Err(Closed));
+}
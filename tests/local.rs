@@ -0,0 +1,62 @@
+use eventuals::*;
+use std::{cell::RefCell, rc::Rc, time::Duration};
+use tokio::{task::LocalSet, test};
+
+#[test]
+async fn map_non_send_values() {
+    LocalSet::new()
+        .run_until(async {
+            let (mut writer, eventual) = LocalEventual::new();
+            let mapped = eventual.map(|v: Rc<u32>| async move { Rc::new(*v + 1) });
+            writer.write(Rc::new(1));
+            // Rc compares by value, not by address.
+            assert_eq!(mapped.value().await, Ok(Rc::new(2)));
+
+            let mut reader = mapped.subscribe();
+            assert_eq!(reader.next().await, Ok(Rc::new(2)));
+            drop(writer);
+            assert_eq!(reader.next().await, Err(Closed));
+            assert_eq!(mapped.value_immediate(), Some(Rc::new(2)));
+        })
+        .await;
+}
+
+#[test]
+async fn joins_values() {
+    LocalSet::new()
+        .run_until(async {
+            let (mut a_writer, a) = LocalEventual::new();
+            let (mut b_writer, b) = LocalEventual::new();
+            a_writer.write(Rc::new("a"));
+            b_writer.write(1);
+            let mut ab = local::join((a, b)).subscribe();
+            assert_eq!(ab.next().await, Ok((Rc::new("a"), 1)));
+            b_writer.write(2);
+            assert_eq!(ab.next().await, Ok((Rc::new("a"), 2)));
+        })
+        .await;
+}
+
+#[test]
+async fn throttles_and_pipes() {
+    LocalSet::new()
+        .run_until(async {
+            let (mut writer, eventual) = LocalEventual::new();
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            let (mut done_writer, done) = LocalEventual::new();
+
+            let seen_pipe = seen.clone();
+            let _pipe = eventual
+                .throttle(Duration::from_millis(5))
+                .pipe(move |v: Rc<u32>| {
+                    seen_pipe.borrow_mut().push(*v);
+                    done_writer.write(*v);
+                });
+
+            writer.write(Rc::new(1));
+            writer.write(Rc::new(2));
+            assert_eq!(done.value().await, Ok(2));
+            assert_eq!(*seen.borrow(), vec![2]);
+        })
+        .await;
+}
@@ -30,6 +30,7 @@ async fn basic() {
 }
 
 #[test]
+#[allow(clippy::await_holding_lock)]
 async fn with_retry_works_eventually() {
     let (mut writer, nums) = Eventual::new();
     writer.write(1);
@@ -0,0 +1,134 @@
+use eventuals::*;
+use std::sync::{Arc, Mutex};
+use tokio::test;
+
+#[test]
+async fn close_is_the_default() {
+    let (mut writer, source) = Eventual::new();
+    let mapped = source.map(|v: u32| async move {
+        if v == 2 {
+            panic!("two");
+        }
+        v
+    });
+    let mut reader = mapped.subscribe();
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(1));
+    assert_eq!(reader.closed_reason(), None);
+    writer.write(2);
+    assert_eq!(reader.next().await, Err(Closed));
+    assert_eq!(
+        mapped.closed_reason(),
+        Some(ClosedReason::Panicked("two".to_owned()))
+    );
+}
+
+#[test]
+async fn skip_continues_with_the_next_value() {
+    let (mut writer, source) = Eventual::new();
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let seen_pipe = seen.clone();
+    let (mut done_writer, done) = Eventual::new();
+    let pipe = source
+        .pipe(move |v: u32| {
+            if v == 3 {
+                panic!("{}", v);
+            }
+            seen_pipe.lock().unwrap().push(v);
+            done_writer.write(v);
+        })
+        .with_panic_policy(PanicPolicy::Skip);
+    writer.write(3);
+    writer.write(4);
+    assert_eq!(done.value().await, Ok(4));
+    assert_eq!(*seen.lock().unwrap(), vec![4]);
+    assert_eq!(pipe.closed_reason(), None);
+}
+
+#[test]
+async fn policies_apply_per_eventual() {
+    let (mut writer, source) = Eventual::new();
+    let skipping = (&source)
+        .map(|v: u32| async move {
+            if v == 1 {
+                panic!("one");
+            }
+            v
+        })
+        .with_panic_policy(PanicPolicy::Skip);
+    let closing = (&source).map(|v: u32| async move {
+        if v == 1 {
+            panic!("one");
+        }
+        v
+    });
+    writer.write(1);
+    assert_eq!(closing.value().await, Err(Closed));
+    writer.write(2);
+    assert_eq!(skipping.value().await, Ok(2));
+    assert_eq!(skipping.closed_reason(), None);
+}
+
+#[test]
+async fn skip_closes_spawned_tasks() {
+    // Tasks passed to spawn have no source to skip ahead in.
+    let spawned = Eventual::<u32>::spawn(|writer| async move {
+        let _writer = writer;
+        panic!("spawned")
+    })
+    .with_panic_policy(PanicPolicy::Skip);
+    assert_eq!(spawned.value().await, Err(Closed));
+    assert_eq!(
+        spawned.closed_reason(),
+        Some(ClosedReason::Panicked("spawned".to_owned()))
+    );
+}
+
+#[test]
+async fn propagate_reports_the_panic_downstream() {
+    let source = Eventual::from_value(1u32);
+    let mapped = source
+        .map(|_: u32| async move { panic!("propagated") })
+        .with_panic_policy(PanicPolicy::Propagate);
+    assert_eq!(mapped.value().await, Err(Closed));
+    assert_eq!(
+        mapped.closed_reason(),
+        Some(ClosedReason::Panicked("propagated".to_owned()))
+    );
+}
+
+#[test]
+async fn writer_dropped_is_not_a_panic() {
+    let (mut writer, source) = Eventual::new();
+    writer.write(4);
+    drop(writer);
+    assert_eq!(source.value().await, Ok(4));
+    assert_eq!(source.closed_reason(), Some(ClosedReason::WriterDropped));
+}
+
+#[test]
+async fn dropping_the_writer_closes_a_running_task() {
+    let eventual = Eventual::<u32>::spawn(|mut writer| async move {
+        writer.write(1);
+        drop(writer);
+        futures::future::pending().await
+    });
+    let mut reader = eventual.subscribe();
+    assert_eq!(reader.next().await, Ok(1));
+    let next = tokio::time::timeout(std::time::Duration::from_secs(1), reader.next());
+    assert_eq!(next.await, Ok(Err(Closed)));
+    assert_eq!(eventual.closed_reason(), Some(ClosedReason::WriterDropped));
+}
+
+#[test]
+async fn panic_is_reported_when_the_writer_is_dropped_while_unwinding() {
+    let eventual = Eventual::<u32>::spawn(|writer| async move {
+        let _writer = writer;
+        panic!("unwinding")
+    });
+    assert_eq!(eventual.value().await, Err(Closed));
+    assert_eq!(
+        eventual.closed_reason(),
+        Some(ClosedReason::Panicked("unwinding".to_owned()))
+    );
+}
@@ -0,0 +1,167 @@
+// These tests run without a tokio runtime. Since the defaults are global, every
+// test in this file must set the same ones.
+use eventuals::{runtime::*, *};
+use futures::{
+    channel::oneshot,
+    executor::{block_on, ThreadPool},
+    future::BoxFuture,
+};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering::SeqCst},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+fn thread_timer(deadline: Instant) -> BoxFuture<'static, ()> {
+    let (sender, receiver) = oneshot::channel();
+    thread::spawn(move || {
+        thread::sleep(deadline.saturating_duration_since(Instant::now()));
+        let _ignore = sender.send(());
+    });
+    Box::pin(async move {
+        let _ignore = receiver.await;
+    })
+}
+
+fn set_defaults() {
+    let pool = ThreadPool::new().unwrap();
+    set_default_spawner(move |f| pool.spawn_ok(f));
+    set_default_timer(thread_timer);
+}
+
+#[test]
+fn combinators_use_default_spawner() {
+    set_defaults();
+    let (mut writer, eventual) = Eventual::new();
+    let mapped = eventual.map(|v: u32| async move { v + 1 });
+    writer.write(1);
+    assert_eq!(block_on(mapped.value()), Ok(2));
+}
+
+#[test]
+fn timer_uses_default_timer() {
+    set_defaults();
+    let interval = Duration::from_millis(2);
+    let mut reader = timer(interval).subscribe();
+    let start = reader.blocking_next().unwrap();
+    let end = reader.blocking_next().unwrap();
+    assert!(end - start >= interval);
+}
+
+#[test]
+fn spawn_on_uses_spawner() {
+    set_defaults();
+    let pool = ThreadPool::new().unwrap();
+    let spawner = move |f| pool.spawn_ok(f);
+    let eventual = Eventual::spawn_on(&spawner, |mut writer| async move {
+        writer.write(thread::current().name().map(String::from));
+        futures::future::pending().await
+    });
+    let name = eventual.blocking_value().unwrap();
+    assert_ne!(name.as_deref(), thread::current().name());
+}
+
+// Counts the tasks spawned with it, so that tests can tell it apart from the
+// default Spawner.
+fn counting_spawner() -> (impl Spawner, Arc<AtomicUsize>) {
+    let pool = ThreadPool::new().unwrap();
+    let count = Arc::new(AtomicUsize::new(0));
+    let spawned = count.clone();
+    let spawner = move |f| {
+        spawned.fetch_add(1, SeqCst);
+        pool.spawn_ok(f)
+    };
+    (spawner, count)
+}
+
+#[test]
+fn lazy_on_uses_spawner() {
+    set_defaults();
+    let (spawner, spawned) = counting_spawner();
+    let eventual = Eventual::lazy_on(&spawner, |mut writer| async move {
+        writer.write(1u32);
+        futures::future::pending().await
+    });
+    assert_eq!(eventual.blocking_value(), Ok(1));
+    assert_eq!(spawned.load(SeqCst), 1);
+}
+
+#[test]
+fn spawn_supervised_on_uses_spawner_and_timer() {
+    set_defaults();
+    let (spawner, spawned) = counting_spawner();
+    let slept = Arc::new(AtomicUsize::new(0));
+    let timer = {
+        let slept = slept.clone();
+        move |deadline| {
+            slept.fetch_add(1, SeqCst);
+            thread_timer(deadline)
+        }
+    };
+    let mut attempts = 0;
+    let policy = RestartPolicy {
+        initial_backoff: Duration::from_millis(1),
+        ..RestartPolicy::default()
+    };
+    let (eventual, restarts) = Eventual::spawn_supervised_on(
+        &spawner,
+        Arc::new(timer),
+        move |mut writer| {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt == 1 {
+                    panic!("first attempt");
+                }
+                writer.write(attempt);
+                futures::future::pending().await
+            }
+        },
+        policy,
+    );
+    assert_eq!(eventual.blocking_value(), Ok(2));
+    assert_eq!(restarts.blocking_value(), Ok(1));
+    assert_eq!(spawned.load(SeqCst), 1);
+    assert_eq!(slept.load(SeqCst), 1);
+}
+
+#[test]
+fn adapters_on_use_spawner() {
+    set_defaults();
+    let (spawner, spawned) = counting_spawner();
+
+    let (sender, receiver) = tokio::sync::watch::channel(1u32);
+    let from_watch = Eventual::from_watch_on(&spawner, receiver);
+    assert_eq!(from_watch.blocking_value(), Ok(1));
+
+    let mut to_watch = from_watch.to_watch_on(&spawner);
+    sender.send(2).unwrap();
+    block_on(async {
+        while *to_watch.borrow_and_update() != Some(2) {
+            to_watch.changed().await.unwrap();
+        }
+    });
+
+    let from_stream = Eventual::from_stream_on(&spawner, futures::stream::iter([3u32]));
+    assert_eq!(from_stream.blocking_value(), Ok(3));
+
+    let (mut writer, source) = Eventual::<u32>::new();
+    let (piped_writer, piped) = oneshot::channel();
+    let mut piped_writer = Some(piped_writer);
+    source
+        .pipe(move |v| {
+            if let Some(sender) = piped_writer.take() {
+                let _ignore = sender.send(v);
+            }
+        })
+        .forever_on(&spawner);
+    writer.write(4);
+    assert_eq!(block_on(piped), Ok(4));
+
+    // from_watch, to_watch, from_stream and the task keeping the pipe alive.
+    // The pipe itself runs with the default Spawner.
+    assert_eq!(spawned.load(SeqCst), 4);
+}
@@ -0,0 +1,45 @@
+use eventuals::*;
+use futures::{stream, SinkExt, StreamExt};
+use tokio::test;
+
+#[test]
+async fn latest_stream_skips_intermediate_values() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut stream = eventual.subscribe().into_latest_stream();
+    writer.write(1);
+    writer.write(2);
+    assert_eq!(stream.next().await, Some(2));
+    writer.write(2);
+    writer.write(3);
+    drop(writer);
+    assert_eq!(stream.collect::<Vec<_>>().await, vec![3]);
+}
+
+#[test]
+async fn from_stream_keeps_the_latest_item() {
+    let eventual = Eventual::from_stream(stream::iter(vec![1, 2, 3]));
+    let mut reader = eventual.subscribe();
+    assert_eq!(reader.next().await, Ok(3));
+    assert_eq!(reader.next().await, Err(Closed));
+    assert_eq!(eventual.closed_reason(), Some(ClosedReason::WriterDropped));
+}
+
+#[test]
+async fn writer_is_a_sink() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut reader = eventual.subscribe();
+    writer.send(1).await.unwrap();
+    assert_eq!(reader.next().await, Ok(1));
+
+    let mut items = stream::iter(vec![Ok(2), Ok(3)]);
+    writer.send_all(&mut items).await.unwrap();
+    assert_eq!(reader.next().await, Ok(3));
+
+    SinkExt::close(&mut writer).await.unwrap();
+    assert_eq!(reader.next().await, Err(Closed));
+
+    // Once readers are gone, sending fails.
+    let (mut writer, eventual) = Eventual::<u32>::new();
+    drop(eventual);
+    assert_eq!(writer.send(1).await, Err(Closed));
+}
@@ -0,0 +1,92 @@
+use eventuals::*;
+use futures::future::pending;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU32, Ordering::SeqCst},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::test;
+
+fn fast() -> RestartPolicy {
+    RestartPolicy {
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(4),
+        max_restarts: None,
+    }
+}
+
+#[test]
+async fn restarts_failed_producers() {
+    let starts = Arc::new(AtomicU32::new(0));
+    let starts_f = starts.clone();
+    let (eventual, restarts) = Eventual::spawn_supervised(
+        move |mut writer| {
+            let start = starts_f.fetch_add(1, SeqCst);
+            async move {
+                writer.write(start.min(1));
+                match start {
+                    0 => Err(Closed),
+                    1 => panic!("failed"),
+                    _ => pending().await,
+                }
+            }
+        },
+        fast(),
+    );
+    let mut restarts = restarts.subscribe();
+    let mut reader = eventual.subscribe();
+    assert_eq!(reader.next().await, Ok(0));
+    assert_eq!(reader.next().await, Ok(1));
+    while restarts.next().await != Ok(2) {}
+    // The last value remains visible across restarts.
+    assert_eq!(eventual.value_immediate(), Some(1));
+    assert_eq!(eventual.closed_reason(), None);
+    assert_eq!(starts.load(SeqCst), 3);
+}
+
+#[test]
+async fn gives_up_after_max_restarts() {
+    let policy = RestartPolicy {
+        max_restarts: Some(1),
+        ..fast()
+    };
+    let (eventual, restarts) =
+        Eventual::<u32>::spawn_supervised(|_writer| async { panic!("failed") }, policy);
+    assert_eq!(eventual.value().await, Err(Closed));
+    assert_eq!(
+        eventual.closed_reason(),
+        Some(ClosedReason::Panicked("failed".to_owned()))
+    );
+    assert_eq!(restarts.value_immediate(), Some(1));
+}
+
+#[derive(Debug)]
+struct Fatal;
+
+impl fmt::Display for Fatal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fatal")
+    }
+}
+
+impl std::error::Error for Fatal {}
+
+#[test]
+async fn producers_which_close_are_not_restarted() {
+    let (eventual, restarts) = Eventual::<u32>::spawn_supervised(
+        |writer| async move {
+            writer.close_with_error(Fatal);
+            Err(Closed)
+        },
+        fast(),
+    );
+    assert_eq!(eventual.value().await, Err(Closed));
+    assert!(matches!(
+        eventual.closed_reason(),
+        Some(ClosedReason::Error(_))
+    ));
+    assert_eq!(restarts.value().await, Ok(0));
+}
@@ -1,5 +1,5 @@
 use eventuals::*;
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use tokio::test;
 
 #[test]
@@ -11,3 +11,29 @@ async fn timer_awaits() {
     let end = reader.next().await.unwrap();
     assert!(end - start >= interval);
 }
+
+#[tokio::test(start_paused = true)]
+async fn throttle_follows_the_paused_clock() {
+    // The wall clock moves on while tokio's clock is paused.
+    std::thread::sleep(Duration::from_millis(200));
+
+    let duration = Duration::from_millis(100);
+    let (mut writer, source) = Eventual::<u32>::new();
+    let throttled = source.throttle(duration);
+    let mut reader = throttled.subscribe();
+    let start = tokio::time::Instant::now();
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(1));
+    let elapsed = start.elapsed();
+    assert!(elapsed >= duration);
+    assert!(elapsed < duration * 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn timer_on_uses_the_given_timer() {
+    let interval = Duration::from_secs(60);
+    let mut reader = timer_on(Arc::new(runtime::TokioTimer), interval).subscribe();
+    let start = reader.next().await.unwrap();
+    let end = reader.next().await.unwrap();
+    assert_eq!(end - start, interval);
+}
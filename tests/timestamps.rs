@@ -0,0 +1,76 @@
+use eventuals::*;
+use std::time::{Duration, Instant};
+use tokio::{test, time::sleep};
+
+#[test]
+async fn snapshots_record_write_time() {
+    let (mut writer, eventual) = Eventual::new();
+    assert_eq!(eventual.last_write_at(), None);
+    let before = Instant::now();
+    writer.write(1);
+    let written_at = eventual.last_write_at().unwrap();
+    assert!(written_at >= before && written_at <= Instant::now());
+
+    let mut reader = eventual.subscribe();
+    assert_eq!(reader.next_timestamped().await, Ok((1, written_at)));
+}
+
+#[test]
+async fn map_and_join_carry_source_timestamps() {
+    let (mut a_writer, a) = Eventual::new();
+    let (mut b_writer, b) = Eventual::new();
+    a_writer.write(1);
+    let a_at = a.last_write_at().unwrap();
+    sleep(Duration::from_millis(5)).await;
+    b_writer.write(2);
+
+    let mapped = a.clone().map(|v| async move {
+        sleep(Duration::from_millis(5)).await;
+        v + 1
+    });
+    let mut reader = mapped.subscribe();
+    assert_eq!(reader.next_timestamped().await, Ok((2, a_at)));
+
+    // The oldest input determines the age of the join.
+    let mut joined = join((mapped, b.clone())).subscribe();
+    assert_eq!(joined.next_timestamped().await, Ok(((2, 2), a_at)));
+
+    b_writer.write(3);
+    assert_eq!(joined.next_timestamped().await, Ok(((2, 3), a_at)));
+    a_writer.write(10);
+    let b_at = b.last_write_at().unwrap();
+    assert_eq!(joined.next_timestamped().await, Ok(((11, 3), b_at)));
+}
+
+#[test]
+#[allow(deprecated)]
+async fn pass_through_combinators_carry_source_timestamps() {
+    let (mut writer, source) = Eventual::<u32>::new();
+    writer.write(1);
+    let written_at = source.last_write_at().unwrap();
+    sleep(Duration::from_millis(5)).await;
+
+    let (_, never) = Eventual::<u32>::new();
+    let results = source.clone().map(|v| async move { Ok::<_, ()>(v) });
+    let outputs = vec![
+        source.clone().throttle(Duration::from_millis(1)),
+        select(vec![source.clone()]),
+        init_with(source.clone(), 0),
+        prefer(source.clone(), never),
+        handle_errors(results.clone(), |()| {}),
+        retry(move |_| {
+            let results = results.clone();
+            async move { results }
+        }),
+    ];
+    for output in outputs {
+        let mut reader = output.subscribe();
+        loop {
+            let (value, at) = reader.next_timestamped().await.unwrap();
+            if value == 1 {
+                assert_eq!(at, written_at);
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use eventuals::*;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+use tokio::test;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+
+type Fields = HashMap<String, String>;
+
+// Records the fields of every span and event.
+#[derive(Clone, Default)]
+struct Recorder {
+    spans: Arc<Mutex<Vec<Fields>>>,
+    events: Arc<Mutex<Vec<Fields>>>,
+}
+
+impl Recorder {
+    fn has_span(&self, kind: &str, name: &str) -> bool {
+        self.spans.lock().unwrap().iter().any(|span| {
+            span.get("kind").map(String::as_str) == Some(kind)
+                && span.get("name").map(String::as_str) == Some(name)
+        })
+    }
+
+    fn has_event(&self, message: &str) -> bool {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|event| event.get("message").map(String::as_str) == Some(message))
+    }
+}
+
+struct Visitor<'a>(&'a mut Fields);
+
+impl Visit for Visitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes) -> Id {
+        let mut fields = Fields::new();
+        span.record(&mut Visitor(&mut fields));
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(fields);
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, span: &Id, values: &Record) {
+        let mut spans = self.spans.lock().unwrap();
+        values.record(&mut Visitor(&mut spans[span.into_u64() as usize - 1]));
+    }
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event) {
+        let mut fields = Fields::new();
+        event.record(&mut Visitor(&mut fields));
+        self.events.lock().unwrap().push(fields);
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+#[test]
+async fn stages_are_named() {
+    let recorder = Recorder::default();
+    let _default = tracing::subscriber::set_default(recorder.clone());
+
+    let (mut writer, source) = Eventual::<u32>::new();
+    let doubled = source.map(|v| async move { v * 2 }).named("doubled");
+    writer.write(1);
+    assert_eq!(doubled.value().await, Ok(2));
+    assert!(recorder.has_span("eventuals::map", "doubled"));
+    assert!(recorder.has_event("write"));
+
+    drop(writer);
+    doubled.subscribe().closed().await;
+    assert!(recorder.has_event("closed"));
+}
+
+#[test]
+async fn redundant_values_are_skipped() {
+    let recorder = Recorder::default();
+    let _default = tracing::subscriber::set_default(recorder.clone());
+
+    let (mut writer, eventual) = Eventual::<u32>::new();
+    let mut reader = eventual.subscribe();
+    writer.write(1);
+    assert_eq!(reader.next().await, Ok(1));
+    writer.write(1);
+    writer.write(2);
+    assert_eq!(reader.next().await, Ok(2));
+    assert!(recorder.has_event("coalesced values"));
+
+    writer.write(2);
+    assert_eq!(reader.try_next(), None);
+    assert!(recorder.has_event("skipped redundant value"));
+}
@@ -0,0 +1,44 @@
+use eventuals::*;
+use tokio::{sync::watch, test};
+
+#[test]
+async fn from_watch() {
+    let (sender, receiver) = watch::channel(1);
+    let eventual = Eventual::from_watch(receiver);
+    let mut reader = eventual.subscribe();
+    assert_eq!(reader.next().await, Ok(1));
+    sender.send(2).unwrap();
+    assert_eq!(reader.next().await, Ok(2));
+    drop(sender);
+    assert_eq!(reader.next().await, Err(Closed));
+    assert_eq!(eventual.closed_reason(), Some(ClosedReason::UpstreamClosed));
+
+    // Dropping the readers drops the receiver.
+    let (sender, receiver) = watch::channel(1);
+    let mapped = receiver.map(|v| async move { v + 1 });
+    assert_eq!(mapped.value().await, Ok(2));
+    drop(mapped);
+    sender.closed().await;
+}
+
+#[test]
+async fn to_watch() {
+    let (mut writer, eventual) = Eventual::new();
+    let mut receiver = eventual.to_watch();
+    assert_eq!(*receiver.borrow(), None);
+    writer.write(1);
+    receiver.changed().await.unwrap();
+    assert_eq!(*receiver.borrow_and_update(), Some(1));
+    writer.write(2);
+    drop(writer);
+    receiver.changed().await.unwrap();
+    assert_eq!(*receiver.borrow_and_update(), Some(2));
+    assert!(receiver.changed().await.is_err());
+
+    // Dropping the receivers stops reading the Eventual.
+    let (writer, eventual) = Eventual::<u32>::new();
+    let receiver = eventual.to_watch();
+    drop(eventual);
+    drop(receiver);
+    writer.closed().await;
+}